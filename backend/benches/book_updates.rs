@@ -0,0 +1,217 @@
+//! Compares the BTreeMap book against the previous Vec-based book.
+//!
+//! Run with `cargo bench --bench book_updates`. By default a synthetic
+//! stream is generated at `BOOK_BENCH_DEPTH` levels (25 if unset); set
+//! `BOOK_BENCH_STREAM` to a v1 recording written with `KRAKEN_RECORD_DIR`
+//! (a `.jsonl.gz` file or a directory of them), or to a plain text file with
+//! one Kraken frame per line, to replay a real session instead.
+
+use orderbook_visualizer::exchange::Venue;
+use orderbook_visualizer::feed::recorder::RECORDING_EXTENSION;
+use orderbook_visualizer::feed::{load_recordings, FeedMessage};
+use orderbook_visualizer::kraken_client::parse_kraken_message;
+use orderbook_visualizer::storage::{OrderbookSnapshot, PriceLevel};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const SYNTHETIC_UPDATES: usize = 50_000;
+const ROUNDS: usize = 5;
+
+fn main() {
+    let depth: usize = std::env::var("BOOK_BENCH_DEPTH")
+        .ok()
+        .and_then(|d| d.parse().ok())
+        .unwrap_or(25);
+
+    let frames = match std::env::var("BOOK_BENCH_STREAM") {
+        Ok(path) => read_stream(Path::new(&path)).unwrap_or_else(|e| panic!("failed to read {}: {}", path, e)),
+        Err(_) => synthetic_stream(SYNTHETIC_UPDATES, depth),
+    };
+
+    println!("replaying {} frames, best of {} rounds", frames.len(), ROUNDS);
+
+    let vec_time = best_of(|| {
+        let mut books = HashMap::new();
+        for frame in &frames {
+            std::hint::black_box(vec_book::parse(frame, &mut books, depth));
+        }
+    });
+
+    let btree_time = best_of(|| {
+        let mut books = HashMap::new();
+        for frame in &frames {
+            if let Ok(Some(FeedMessage::Snapshot(snapshot))) = parse_kraken_message(frame, &mut books) {
+                std::hint::black_box(snapshot);
+            }
+        }
+    });
+
+    report("vec (retain + sort)", vec_time, frames.len());
+    report("btree", btree_time, frames.len());
+    println!(
+        "speedup: {:.2}x",
+        vec_time.as_secs_f64() / btree_time.as_secs_f64()
+    );
+}
+
+/// Frames of a recording or recording directory, or the lines of a plain text file
+fn read_stream(path: &Path) -> std::io::Result<Vec<String>> {
+    if path.is_dir() || path.to_string_lossy().ends_with(RECORDING_EXTENSION) {
+        let frames = load_recordings(path)?;
+        return Ok(frames.into_iter().map(|recorded| recorded.frame).collect());
+    }
+    Ok(std::fs::read_to_string(path)?.lines().map(str::to_string).collect())
+}
+
+fn best_of(mut run: impl FnMut()) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, elapsed: Duration, frames: usize) {
+    println!(
+        "{:<20} {:>10.2?} total {:>8.0} ns/frame",
+        name,
+        elapsed,
+        elapsed.as_nanos() as f64 / frames as f64
+    );
+}
+
+/// Deterministic session: one snapshot followed by small updates spread
+/// over the subscribed depth, like a live XBT/USD feed.
+fn synthetic_stream(updates: usize, depth: usize) -> Vec<String> {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+
+    let level = |price: u64, volume: u64| {
+        format!(
+            "[\"{}.{}\",\"{}.{:08}\",\"1700000000.000000\"]",
+            price / 10,
+            price % 10,
+            volume / 100_000_000,
+            volume % 100_000_000
+        )
+    };
+
+    let mid = 500_000u64;
+    let levels = depth as u64;
+    let asks: Vec<String> = (1..=levels).map(|i| level(mid + i, 10_000_000 * i)).collect();
+    let bids: Vec<String> = (1..=levels).map(|i| level(mid - i, 10_000_000 * i)).collect();
+    let mut frames = vec![format!(
+        "[336,{{\"as\":[{}],\"bs\":[{}]}},\"book-{}\",\"XBT/USD\"]",
+        asks.join(","),
+        bids.join(","),
+        depth
+    )];
+
+    for _ in 0..updates {
+        let side = if next() % 2 == 0 { "a" } else { "b" };
+        let offset = 1 + next() % (levels + levels / 5);
+        let price = if side == "a" { mid + offset } else { mid - offset };
+        let volume = if next() % 5 == 0 { 0 } else { next() % 500_000_000 };
+        frames.push(format!(
+            "[336,{{\"{}\":[{}]}},\"book-{}\",\"XBT/USD\"]",
+            side,
+            level(price, volume),
+            depth
+        ));
+    }
+
+    frames
+}
+
+/// The book implementation the client used before the BTreeMap rewrite
+mod vec_book {
+    use super::*;
+    use chrono::Utc;
+
+    type Books = HashMap<String, (Vec<PriceLevel>, Vec<PriceLevel>)>;
+
+    pub fn parse(text: &str, orderbooks: &mut Books, depth: usize) -> Option<OrderbookSnapshot> {
+        let value: serde_json::Value = serde_json::from_str(text).ok()?;
+        let arr = value.as_array()?;
+        let pair = arr.last()?.as_str()?;
+        let (bids, asks) = orderbooks.entry(pair.to_string()).or_default();
+
+        for data in &arr[1..arr.len() - 2] {
+            let obj = data.as_object()?;
+            if let Some(snap) = obj.get("as") {
+                *asks = parse_levels(snap);
+            }
+            if let Some(snap) = obj.get("bs") {
+                *bids = parse_levels(snap);
+            }
+            if let Some(updates) = obj.get("a") {
+                apply_updates(asks, updates, false, depth);
+            }
+            if let Some(updates) = obj.get("b") {
+                apply_updates(bids, updates, true, depth);
+            }
+        }
+
+        Some(OrderbookSnapshot {
+            venue: Venue::Kraken,
+            symbol: pair.to_string(),
+            timestamp: Utc::now(),
+            received_at: None,
+            bids: bids.clone(),
+            asks: asks.clone(),
+            checksum: None,
+            sequence: None,
+            stale: false,
+        })
+    }
+
+    fn parse_levels(value: &serde_json::Value) -> Vec<PriceLevel> {
+        value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                let level = item.as_array()?;
+                Some(PriceLevel {
+                    price: Decimal::from_str(level.first()?.as_str()?).ok()?,
+                    volume: Decimal::from_str(level.get(1)?.as_str()?).ok()?,
+                    order_count: None,
+                    timestamp: None,
+                })
+            })
+            .collect()
+    }
+
+    fn apply_updates(
+        levels: &mut Vec<PriceLevel>,
+        updates: &serde_json::Value,
+        is_bid: bool,
+        depth: usize,
+    ) {
+        for update in parse_levels(updates) {
+            levels.retain(|l| l.price != update.price);
+            if update.volume > Decimal::ZERO {
+                levels.push(update);
+            }
+            levels.sort_by(|a, b| {
+                if is_bid {
+                    b.price.cmp(&a.price)
+                } else {
+                    a.price.cmp(&b.price)
+                }
+            });
+            levels.truncate(depth);
+        }
+    }
+}
@@ -0,0 +1,720 @@
+//! HTTP and WebSocket API served to the frontend
+
+use crate::consolidated::ConsolidatedBook;
+use crate::delta::{BookDelta, DeltaStream, StreamUpdate};
+use crate::exchange::Venue;
+use crate::feed::{BookSubscription, FeedControl, SubscriptionStatus, DEFAULT_BOOK_DEPTH};
+use crate::orderbook_manager::{LiveBook, OrderbookManager};
+use crate::storage::{OrderbookSnapshot, Trade};
+use crate::trading::{OrderIntent, TradingService};
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+use warp::Filter;
+
+/// API query parameters for history endpoint
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    from: Option<String>,
+    to: Option<String>,
+    /// Maximum levels per side to return
+    depth: Option<usize>,
+}
+
+/// API query parameters for trades endpoint
+#[derive(Debug, Deserialize)]
+struct TradesQuery {
+    from: Option<String>,
+    to: Option<String>,
+    /// Maximum number of most recent trades to return
+    limit: Option<usize>,
+}
+
+/// API query parameters for OHLC endpoint
+#[derive(Debug, Deserialize)]
+struct OhlcQuery {
+    /// Maximum number of most recent candles to return
+    limit: Option<usize>,
+}
+
+/// API query parameters for current orderbook and stream endpoints
+#[derive(Debug, Deserialize)]
+struct DepthQuery {
+    /// Maximum levels per side to return (defaults to the subscribed depth)
+    depth: Option<usize>,
+    /// Exchange whose book to return, or `all` for the consolidated book
+    /// (defaults to Kraken, or the implied book of a synthetic pair)
+    venue: Option<VenueQuery>,
+}
+
+/// Book selected by the `venue` query parameter
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum VenueQuery {
+    /// Every venue's levels merged into one book
+    All,
+    #[serde(untagged)]
+    One(Venue),
+}
+
+impl VenueQuery {
+    fn includes(&self, venue: Venue) -> bool {
+        match self {
+            VenueQuery::All => true,
+            VenueQuery::One(one) => *one == venue,
+        }
+    }
+}
+
+/// Body of an admin subscription request
+#[derive(Debug, Deserialize)]
+struct SubscriptionRequest {
+    /// Pair in any known naming (e.g. `ADA/USD`, `BTC/USD`, `XXBTZUSD`)
+    pair: String,
+    /// Book depth (defaults to 25)
+    depth: Option<usize>,
+}
+
+/// WebSocket message types
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WsMessage {
+    /// Full book; `sequence` is that of the last delta it includes
+    #[serde(rename = "snapshot")]
+    Snapshot {
+        data: OrderbookSnapshot,
+        #[serde(default)]
+        sequence: u64,
+    },
+    /// Changes since the previous snapshot or delta of the same book
+    #[serde(rename = "delta")]
+    Delta { data: BookDelta },
+    #[serde(rename = "consolidated")]
+    Consolidated { data: ConsolidatedBook },
+    #[serde(rename = "trade")]
+    Trade { data: Trade },
+    #[serde(rename = "subscription")]
+    Subscription { symbol: String, status: SubscriptionStatus },
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+/// All API routes, with CORS enabled for any origin
+pub fn routes(
+    manager: Arc<OrderbookManager>,
+    feed: FeedControl,
+    trading_service: Arc<RwLock<TradingService>>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let cors = warp::cors()
+        .allow_any_origin()
+        .allow_headers(vec!["content-type"])
+        .allow_methods(vec!["GET", "POST", "DELETE", "OPTIONS"]);
+
+    // GET /api/orderbook/:base/:quote?depth=<n>&venue=<venue|all> - Get current orderbook (e.g., /api/orderbook/XBT/USD)
+    let manager_current = manager.clone();
+    let current_route = warp::path!("api" / "orderbook" / String / String)
+        .and(warp::get())
+        .and(warp::query::<DepthQuery>())
+        .map(move |base: String, quote: String, query: DepthQuery| {
+            let symbol = manager_current.symbols().key(&base, &quote);
+            let manager = manager_current.clone();
+            let venue = match query.venue.unwrap_or_else(|| VenueQuery::One(manager.default_venue(&symbol))) {
+                VenueQuery::One(venue) => venue,
+                VenueQuery::All => {
+                    tracing::debug!("Looking up consolidated orderbook for symbol: {}", symbol);
+                    return match manager.get_consolidated(&symbol) {
+                        Some(mut book) => {
+                            if let Some(depth) = query.depth {
+                                book.truncate(depth);
+                            }
+                            warp::reply::json(&book)
+                        }
+                        None => warp::reply::json(&serde_json::json!({
+                            "error": "No venue has a book for this symbol",
+                            "requested": symbol,
+                            "subscription": manager.get_subscription_status(&symbol)
+                        })),
+                    };
+                }
+            };
+            tracing::debug!("Looking up {} orderbook for symbol: {}", venue, symbol);
+            if let Some(mut snapshot) = manager.get_venue_book(venue, &symbol) {
+                if let Some(depth) = query.depth {
+                    snapshot.truncate(depth);
+                }
+                warp::reply::json(&snapshot)
+            } else if venue != Venue::Kraken {
+                warp::reply::json(&serde_json::json!({
+                    "error": format!("No {} book for this symbol", venue),
+                    "requested": symbol,
+                    "venue": venue
+                }))
+            } else {
+                let status = manager.get_subscription_status(&symbol);
+                warp::reply::json(&serde_json::json!({
+                    "error": not_available_reason(status.as_ref()),
+                    "requested": symbol,
+                    "subscription": status
+                }))
+            }
+        });
+
+    // GET /api/orderbook/:base/:quote/history?from=<ts>&to=<ts>&depth=<n> - Get history
+    let manager_history = manager.clone();
+    let history_route = warp::path!("api" / "orderbook" / String / String / "history")
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .map(move |base: String, quote: String, query: HistoryQuery| {
+            let symbol = manager_history.symbols().key(&base, &quote);
+            let manager = manager_history.clone();
+
+            let from = query
+                .from
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(|| Utc::now() - chrono::Duration::hours(24));
+
+            let to = query
+                .to
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(Utc::now);
+
+            match manager.get_history(&symbol, from, to) {
+                Ok(mut snapshots) => {
+                    if let Some(depth) = query.depth {
+                        snapshots.iter_mut().for_each(|s| s.truncate(depth));
+                    }
+                    warp::reply::json(&snapshots)
+                }
+                Err(e) => warp::reply::json(&serde_json::json!({
+                    "error": format!("Failed to get history: {}", e)
+                })),
+            }
+        });
+
+    // GET /api/orderbook/:base/:quote/snapshot/:timestamp - Get snapshot at time
+    let manager_snapshot = manager.clone();
+    let snapshot_route = warp::path!("api" / "orderbook" / String / String / "snapshot" / String)
+        .and(warp::get())
+        .map(move |base: String, quote: String, timestamp: String| {
+            let symbol = manager_snapshot.symbols().key(&base, &quote);
+            let manager = manager_snapshot.clone();
+
+            if let Ok(dt) = DateTime::parse_from_rfc3339(&timestamp) {
+                let dt_utc = dt.with_timezone(&Utc);
+                match manager.get_at_time(&symbol, dt_utc) {
+                    Ok(Some(snapshot)) => warp::reply::json(&snapshot),
+                    Ok(None) => warp::reply::json(&serde_json::json!({
+                        "error": "No snapshot found at that time"
+                    })),
+                    Err(e) => warp::reply::json(&serde_json::json!({
+                        "error": format!("Failed to get snapshot: {}", e)
+                    })),
+                }
+            } else {
+                warp::reply::json(&serde_json::json!({
+                    "error": "Invalid timestamp format"
+                }))
+            }
+        });
+
+    // GET /api/orderbook/:base/:quote/stats - Get storage stats
+    let manager_stats = manager.clone();
+    let stats_route = warp::path!("api" / "orderbook" / String / String / "stats")
+        .and(warp::get())
+        .map(move |base: String, quote: String| {
+            let symbol = manager_stats.symbols().key(&base, &quote);
+            let manager = manager_stats.clone();
+            match manager.get_stats(&symbol) {
+                Ok(stats) => warp::reply::json(&stats),
+                Err(e) => warp::reply::json(&serde_json::json!({
+                    "error": format!("Failed to get stats: {}", e)
+                })),
+            }
+        });
+
+    // WebSocket route - ws://localhost:3033/ws/orderbook/:base/:quote?depth=<n>&venue=<venue|all>
+    let manager_ws = manager.clone();
+    let ws_route = warp::path!("ws" / "orderbook" / String / String)
+        .and(warp::query::<DepthQuery>())
+        .and(warp::ws())
+        .map(move |base: String, quote: String, query: DepthQuery, ws: warp::ws::Ws| {
+            let symbol = manager_ws.symbols().key(&base, &quote);
+            let manager = manager_ws.clone();
+            let venue = query.venue.unwrap_or_else(|| VenueQuery::One(manager.default_venue(&symbol)));
+            ws.on_upgrade(move |socket| websocket_handler(socket, symbol, query.depth, venue, manager))
+        });
+
+    // GET /api/trades/:base/:quote?from=<ts>&to=<ts>&limit=<n> - Get executed trades
+    let manager_trades = manager.clone();
+    let trades_route = warp::path!("api" / "trades" / String / String)
+        .and(warp::get())
+        .and(warp::query::<TradesQuery>())
+        .map(move |base: String, quote: String, query: TradesQuery| {
+            let symbol = manager_trades.symbols().key(&base, &quote);
+            let manager = manager_trades.clone();
+
+            let from = query
+                .from
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(|| Utc::now() - chrono::Duration::hours(1));
+
+            let to = query
+                .to
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(Utc::now);
+
+            match manager.get_trades(&symbol, from, to, query.limit.unwrap_or(500)) {
+                Ok(trades) => warp::reply::json(&trades),
+                Err(e) => warp::reply::json(&serde_json::json!({
+                    "error": format!("Failed to get trades: {}", e)
+                })),
+            }
+        });
+
+    // GET /api/ticker/:base/:quote - Get 24h ticker and best bid/offer
+    let manager_ticker = manager.clone();
+    let ticker_route = warp::path!("api" / "ticker" / String / String)
+        .and(warp::get())
+        .map(move |base: String, quote: String| {
+            let symbol = manager_ticker.symbols().key(&base, &quote);
+            let ticker = manager_ticker.get_ticker(&symbol);
+            let spread = manager_ticker.get_spread(&symbol);
+            if ticker.is_none() && spread.is_none() {
+                warp::reply::json(&serde_json::json!({
+                    "error": "No ticker data",
+                    "requested": symbol
+                }))
+            } else {
+                warp::reply::json(&serde_json::json!({
+                    "symbol": symbol,
+                    "ticker": ticker,
+                    "spread": spread
+                }))
+            }
+        });
+
+    // GET /api/ohlc/:base/:quote?limit=<n> - Get recent candles
+    let manager_ohlc = manager.clone();
+    let ohlc_route = warp::path!("api" / "ohlc" / String / String)
+        .and(warp::get())
+        .and(warp::query::<OhlcQuery>())
+        .map(move |base: String, quote: String, query: OhlcQuery| {
+            let symbol = manager_ohlc.symbols().key(&base, &quote);
+            let candles = manager_ohlc.get_candles(&symbol, query.limit.unwrap_or(720));
+            warp::reply::json(&candles)
+        });
+
+    // Trades WebSocket route - ws://localhost:3033/ws/trades/:base/:quote
+    let manager_trades_ws = manager.clone();
+    let trades_ws_route = warp::path!("ws" / "trades" / String / String)
+        .and(warp::ws())
+        .map(move |base: String, quote: String, ws: warp::ws::Ws| {
+            let symbol = manager_trades_ws.symbols().key(&base, &quote);
+            let manager = manager_trades_ws.clone();
+            ws.on_upgrade(move |socket| trades_websocket_handler(socket, symbol, manager))
+        });
+
+    // GET /api/symbols - Known pairs with their names, tick size and order minimums
+    let manager_symbols = manager.clone();
+    let symbols_route = warp::path!("api" / "symbols")
+        .and(warp::get())
+        .map(move || {
+            let symbols: Vec<_> = manager_symbols.symbols().iter().collect();
+            warp::reply::json(&symbols)
+        });
+
+    // GET /api/symbols/:base/:quote - Metadata of one pair
+    let manager_symbol = manager.clone();
+    let symbol_route = warp::path!("api" / "symbols" / String / String)
+        .and(warp::get())
+        .map(move |base: String, quote: String| {
+            let symbols = manager_symbol.symbols();
+            match symbols.resolve(&format!("{}/{}", base, quote)) {
+                Ok(symbol) => match symbols.info(&symbol) {
+                    Some(info) => warp::reply::json(info),
+                    None => warp::reply::json(&serde_json::json!({
+                        "error": "No metadata for this pair",
+                        "requested": symbol
+                    })),
+                },
+                Err(e) => warp::reply::json(&serde_json::json!({ "error": e })),
+            }
+        });
+
+    // Health check
+    let health_route = warp::path!("api" / "health")
+        .and(warp::get())
+        .map(|| {
+            warp::reply::json(&serde_json::json!({
+                "status": "healthy",
+                "service": "orderbook-visualizer",
+                "timestamp": Utc::now().to_rfc3339()
+            }))
+        });
+
+    // GET /api/connection - Exchange feed connection status
+    let manager_connection = manager.clone();
+    let connection_route = warp::path!("api" / "connection")
+        .and(warp::get())
+        .map(move || warp::reply::json(&manager_connection.connection_status()));
+
+    // ========== Admin Routes ==========
+
+    // GET /api/admin/subscriptions - List tracked symbols
+    let feed_list = feed.clone();
+    let subscriptions_route = warp::path!("api" / "admin" / "subscriptions")
+        .and(warp::get())
+        .map(move || warp::reply::json(&feed_list.subscriptions()));
+
+    // POST /api/admin/subscriptions - Track a symbol, or change its depth
+    let feed_subscribe = feed.clone();
+    let manager_subscribe = manager.clone();
+    let subscribe_route = warp::path!("api" / "admin" / "subscriptions")
+        .and(warp::post())
+        .and(warp::body::json())
+        .map(move |request: SubscriptionRequest| {
+            let result = manager_subscribe
+                .symbols()
+                .resolve(&request.pair)
+                .and_then(|pair| BookSubscription::for_symbol(pair, request.depth.unwrap_or(DEFAULT_BOOK_DEPTH)))
+                .and_then(|sub| feed_subscribe.subscribe(sub));
+            match result {
+                Ok(()) => warp::reply::json(&feed_subscribe.subscriptions()),
+                Err(e) => warp::reply::json(&serde_json::json!({ "error": e })),
+            }
+        });
+
+    // DELETE /api/admin/subscriptions/:base/:quote - Stop tracking a symbol
+    let feed_unsubscribe = feed.clone();
+    let manager_unsubscribe = manager.clone();
+    let unsubscribe_route = warp::path!("api" / "admin" / "subscriptions" / String / String)
+        .and(warp::delete())
+        .map(move |base: String, quote: String| {
+            let symbol = manager_unsubscribe.symbols().key(&base, &quote);
+            match feed_unsubscribe.unsubscribe(&symbol) {
+                Ok(()) => {
+                    manager_unsubscribe.remove_symbol(&symbol);
+                    warp::reply::json(&feed_unsubscribe.subscriptions())
+                }
+                Err(e) => warp::reply::json(&serde_json::json!({ "error": e })),
+            }
+        });
+
+    // ========== Trading Routes ==========
+
+    // GET /api/trading/status - Get trading mode and status
+    let trading_status = trading_service.clone();
+    let trading_status_route = warp::path!("api" / "trading" / "status")
+        .and(warp::get())
+        .and_then(move || {
+            let service = trading_status.clone();
+            async move {
+                let service = service.read().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
+                    "mode": service.mode(),
+                    "live_available": service.is_live_available(),
+                })))
+            }
+        });
+
+    // GET /api/trading/account - Get account info
+    let trading_account = trading_service.clone();
+    let trading_account_route = warp::path!("api" / "trading" / "account")
+        .and(warp::get())
+        .and_then(move || {
+            let service = trading_account.clone();
+            async move {
+                let service = service.read().await;
+                let info = service.get_account_info().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&info))
+            }
+        });
+
+    // POST /api/trading/order - Place an order
+    let trading_order = trading_service.clone();
+    let trading_order_route = warp::path!("api" / "trading" / "order")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(move |intent: OrderIntent| {
+            let service = trading_order.clone();
+            async move {
+                let service = service.read().await;
+                let result = service.execute_order(intent).await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&result))
+            }
+        });
+
+    // DELETE /api/trading/order/:txid - Cancel an order
+    let trading_cancel = trading_service.clone();
+    let trading_cancel_route = warp::path!("api" / "trading" / "order" / String)
+        .and(warp::delete())
+        .and_then(move |txid: String| {
+            let service = trading_cancel.clone();
+            async move {
+                let service = service.read().await;
+                let result = service.cancel_order(&txid).await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&result))
+            }
+        });
+
+    // DELETE /api/trading/orders - Cancel all orders
+    let trading_cancel_all = trading_service.clone();
+    let trading_cancel_all_route = warp::path!("api" / "trading" / "orders")
+        .and(warp::delete())
+        .and_then(move || {
+            let service = trading_cancel_all.clone();
+            async move {
+                let service = service.read().await;
+                let result = service.cancel_all().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&result))
+            }
+        });
+
+    // GET /api/trading/paper-orders - Get paper order history
+    let trading_paper = trading_service.clone();
+    let trading_paper_route = warp::path!("api" / "trading" / "paper-orders")
+        .and(warp::get())
+        .and_then(move || {
+            let service = trading_paper.clone();
+            async move {
+                let service = service.read().await;
+                let orders = service.get_paper_orders().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&orders))
+            }
+        });
+
+    // Combine routes
+    current_route
+        .or(history_route)
+        .or(snapshot_route)
+        .or(stats_route)
+        .or(ws_route)
+        .or(trades_route)
+        .or(trades_ws_route)
+        .or(ticker_route)
+        .or(ohlc_route)
+        .or(symbols_route)
+        .or(symbol_route)
+        .or(health_route)
+        .or(connection_route)
+        .or(subscriptions_route)
+        .or(subscribe_route)
+        .or(unsubscribe_route)
+        .or(trading_status_route)
+        .or(trading_account_route)
+        .or(trading_order_route)
+        .or(trading_cancel_route)
+        .or(trading_cancel_all_route)
+        .or(trading_paper_route)
+        .with(cors)
+}
+
+/// Why there is no book for a symbol, given its subscription status
+fn not_available_reason(status: Option<&SubscriptionStatus>) -> String {
+    match status {
+        None => "Symbol not tracked".to_string(),
+        Some(SubscriptionStatus::Pending) => "Waiting for Kraken to confirm the subscription".to_string(),
+        Some(SubscriptionStatus::Subscribed) => "Waiting for the first snapshot".to_string(),
+        Some(SubscriptionStatus::Rejected { reason }) => format!("Subscription rejected: {}", reason),
+    }
+}
+
+/// Consolidated book message limited to `depth` levels per side
+fn consolidated_message(manager: &OrderbookManager, symbol: &str, depth: Option<usize>) -> Option<WsMessage> {
+    let mut data = manager.get_consolidated(symbol)?;
+    if let Some(depth) = depth {
+        data.truncate(depth);
+    }
+    Some(WsMessage::Consolidated { data })
+}
+
+/// Snapshot message restarting `stream` from `live`
+fn snapshot_message(stream: &mut DeltaStream, live: LiveBook) -> WsMessage {
+    WsMessage::Snapshot {
+        sequence: live.sequence,
+        data: stream.restart(live.snapshot, live.sequence),
+    }
+}
+
+/// Current book message for `venue`, restarting `stream` for a single venue
+fn current_message(
+    manager: &OrderbookManager,
+    symbol: &str,
+    depth: Option<usize>,
+    venue: VenueQuery,
+    stream: &mut DeltaStream,
+) -> Option<WsMessage> {
+    match venue {
+        VenueQuery::All => consolidated_message(manager, symbol, depth),
+        VenueQuery::One(one) => manager.get_live_book(one, symbol).map(|live| snapshot_message(stream, live)),
+    }
+}
+
+/// WebSocket handler for real-time orderbook updates.
+///
+/// A single venue's book is sent in full once, then as deltas; the
+/// consolidated book is resent in full on every change.
+async fn websocket_handler(
+    ws: warp::ws::WebSocket,
+    symbol: String,
+    depth: Option<usize>,
+    venue: VenueQuery,
+    manager: Arc<OrderbookManager>,
+) {
+    let (mut ws_tx, mut ws_rx) = ws.split();
+    let mut update_rx = manager.subscribe_updates(&symbol);
+    let mut subscription_rx = manager.subscribe_subscription_status();
+
+    tracing::info!("WebSocket client connected for symbol: {} ({:?})", symbol, venue);
+
+    // Send current snapshot on connection, or why there is none yet
+    let mut stream = DeltaStream::new(depth);
+    let initial = match current_message(&manager, &symbol, depth, venue, &mut stream) {
+        Some(message) => message,
+        None if venue != VenueQuery::One(Venue::Kraken) => WsMessage::Error {
+            message: "Waiting for the first snapshot".to_string(),
+        },
+        None => match manager.get_subscription_status(&symbol) {
+            Some(status) => WsMessage::Subscription {
+                symbol: symbol.clone(),
+                status,
+            },
+            None => WsMessage::Error {
+                message: not_available_reason(None),
+            },
+        },
+    };
+    if let Ok(json) = serde_json::to_string(&initial) {
+        let _ = ws_tx.send(warp::ws::Message::text(json)).await;
+    }
+
+    // Handle updates and client messages
+    tokio::select! {
+        _ = async {
+            loop {
+                let msg = tokio::select! {
+                    update = update_rx.recv() => match update {
+                        // The channel carries every venue's book of the symbol
+                        Ok(delta) if venue.includes(delta.venue) => match venue {
+                            // Rebuilt from the latest book of every venue
+                            VenueQuery::All => match consolidated_message(&manager, &symbol, depth) {
+                                Some(message) => message,
+                                None => continue,
+                            },
+                            VenueQuery::One(_) => match stream.next(delta) {
+                                StreamUpdate::Send(data) => WsMessage::Delta { data },
+                                StreamUpdate::Skip => continue,
+                                // Missed part of the stream; start over from the current book
+                                StreamUpdate::Gap => match current_message(&manager, &symbol, depth, venue, &mut stream) {
+                                    Some(message) => message,
+                                    None => continue,
+                                },
+                            },
+                        },
+                        Ok(_) => continue,
+                        // Fell behind; skip the missed deltas and start over from the current book
+                        Err(RecvError::Lagged(skipped)) => {
+                            tracing::debug!("WebSocket client for {} lagged by {} updates", symbol, skipped);
+                            match current_message(&manager, &symbol, depth, venue, &mut stream) {
+                                Some(message) => message,
+                                None => continue,
+                            }
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    change = subscription_rx.recv() => match change {
+                        // Subscription status is only tracked for Kraken
+                        Ok(change) if change.symbol == symbol && venue.includes(Venue::Kraken) => WsMessage::Subscription {
+                            symbol: change.symbol,
+                            status: change.status,
+                        },
+                        Ok(_) => continue,
+                        Err(_) => break,
+                    },
+                };
+                if let Ok(json) = serde_json::to_string(&msg) {
+                    if ws_tx.send(warp::ws::Message::text(json)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        } => {},
+        _ = async {
+            while let Some(result) = ws_rx.next().await {
+                match result {
+                    Ok(msg) => {
+                        if msg.is_close() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("WebSocket error: {}", e);
+                        break;
+                    }
+                }
+            }
+        } => {},
+    }
+
+    tracing::info!("WebSocket client disconnected for symbol: {}", symbol);
+}
+
+/// WebSocket handler for real-time trades
+async fn trades_websocket_handler(
+    ws: warp::ws::WebSocket,
+    symbol: String,
+    manager: Arc<OrderbookManager>,
+) {
+    let (mut ws_tx, mut ws_rx) = ws.split();
+    let mut trade_rx = manager.subscribe_trades();
+
+    tracing::info!("Trades WebSocket client connected for symbol: {}", symbol);
+
+    tokio::select! {
+        _ = async {
+            loop {
+                let trade = match trade_rx.recv().await {
+                    // Only send trades for the requested symbol
+                    Ok(trade) if trade.symbol == symbol => trade,
+                    Ok(_) => continue,
+                    // Trades are not resent; carry on with the next ones
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Trades WebSocket client for {} missed {} trades", symbol, skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let msg = WsMessage::Trade { data: trade };
+                if let Ok(json) = serde_json::to_string(&msg) {
+                    if ws_tx.send(warp::ws::Message::text(json)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        } => {},
+        _ = async {
+            while let Some(result) = ws_rx.next().await {
+                match result {
+                    Ok(msg) => {
+                        if msg.is_close() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("WebSocket error: {}", e);
+                        break;
+                    }
+                }
+            }
+        } => {},
+    }
+
+    tracing::info!("Trades WebSocket client disconnected for symbol: {}", symbol);
+}
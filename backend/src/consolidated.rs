@@ -0,0 +1,202 @@
+//! Consolidated orderbook merging the books of several venues
+//!
+//! Levels keep their source venue instead of being summed per price, so the
+//! merged book shows both aggregate depth and where it sits. When the best bid
+//! on one venue is above the best ask on another, the book reports the
+//! cross-venue arbitrage.
+
+use crate::exchange::Venue;
+use crate::storage::{OrderbookSnapshot, PriceLevel};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Price level tagged with the venue it comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VenueLevel {
+    pub venue: Venue,
+    #[serde(flatten)]
+    pub level: PriceLevel,
+}
+
+/// Best bid crossing the best ask of another venue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Arbitrage {
+    /// Venue offering the best ask
+    pub buy_venue: Venue,
+    /// Venue bidding the best bid
+    pub sell_venue: Venue,
+    /// Best bid minus best ask
+    pub spread: Decimal,
+    /// Volume available at both top levels
+    pub volume: Decimal,
+}
+
+/// Book merging every venue's levels for one symbol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidatedBook {
+    pub symbol: String,
+    /// Latest exchange time across the merged books
+    pub timestamp: DateTime<Utc>,
+    /// Venues whose books were merged
+    pub venues: Vec<Venue>,
+    /// Best first; levels at the same price are ordered by venue
+    pub bids: Vec<VenueLevel>,
+    pub asks: Vec<VenueLevel>,
+    /// Best ask minus best bid across venues; negative while venues are crossed
+    pub spread: Option<Decimal>,
+    pub arbitrage: Option<Arbitrage>,
+    /// Set while any merged book is stale
+    pub stale: bool,
+}
+
+impl ConsolidatedBook {
+    /// Merge the books of one symbol, or `None` when there are none
+    pub fn merge(symbol: &str, books: &[OrderbookSnapshot]) -> Option<Self> {
+        let timestamp = books.iter().map(|book| book.timestamp).max()?;
+
+        let mut venues: Vec<Venue> = books.iter().map(|book| book.venue).collect();
+        venues.sort();
+        venues.dedup();
+
+        let tagged = |book: &OrderbookSnapshot, levels: &[PriceLevel]| {
+            levels
+                .iter()
+                .map(|level| VenueLevel {
+                    venue: book.venue,
+                    level: level.clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut bids: Vec<VenueLevel> = books.iter().flat_map(|book| tagged(book, &book.bids)).collect();
+        let mut asks: Vec<VenueLevel> = books.iter().flat_map(|book| tagged(book, &book.asks)).collect();
+        bids.sort_by(|a, b| b.level.price.cmp(&a.level.price).then(a.venue.cmp(&b.venue)));
+        asks.sort_by(|a, b| a.level.price.cmp(&b.level.price).then(a.venue.cmp(&b.venue)));
+
+        let (spread, arbitrage) = match (bids.first(), asks.first()) {
+            (Some(bid), Some(ask)) => {
+                let arbitrage = (bid.level.price > ask.level.price).then(|| Arbitrage {
+                    buy_venue: ask.venue,
+                    sell_venue: bid.venue,
+                    spread: bid.level.price - ask.level.price,
+                    volume: bid.level.volume.min(ask.level.volume),
+                });
+                (Some(ask.level.price - bid.level.price), arbitrage)
+            }
+            _ => (None, None),
+        };
+
+        Some(Self {
+            symbol: symbol.to_string(),
+            timestamp,
+            venues,
+            bids,
+            asks,
+            spread,
+            arbitrage,
+            stale: books.iter().any(|book| book.stale),
+        })
+    }
+
+    /// Keep at most `depth` levels on each side
+    pub fn truncate(&mut self, depth: usize) {
+        self.bids.truncate(depth);
+        self.asks.truncate(depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn book(venue: Venue, bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) -> OrderbookSnapshot {
+        let levels = |levels: &[(Decimal, Decimal)]| {
+            levels
+                .iter()
+                .map(|&(price, volume)| PriceLevel {
+                    price,
+                    volume,
+                    order_count: None,
+                    timestamp: None,
+                })
+                .collect()
+        };
+        OrderbookSnapshot {
+            venue,
+            symbol: "XBT/USD".to_string(),
+            timestamp: Utc::now(),
+            received_at: None,
+            bids: levels(bids),
+            asks: levels(asks),
+            checksum: None,
+            sequence: None,
+            stale: false,
+        }
+    }
+
+    fn prices(levels: &[VenueLevel]) -> Vec<(Venue, Decimal)> {
+        levels.iter().map(|level| (level.venue, level.level.price)).collect()
+    }
+
+    #[test]
+    fn merges_levels_best_first_tagged_by_venue() {
+        let kraken = book(
+            Venue::Kraken,
+            &[(dec("100"), dec("1")), (dec("99"), dec("2"))],
+            &[(dec("101"), dec("1")), (dec("103"), dec("2"))],
+        );
+        let coinbase = book(
+            Venue::Coinbase,
+            &[(dec("100"), dec("3")), (dec("98"), dec("1"))],
+            &[(dec("102"), dec("1"))],
+        );
+
+        let merged = ConsolidatedBook::merge("XBT/USD", &[coinbase, kraken]).unwrap();
+
+        assert_eq!(merged.venues, vec![Venue::Kraken, Venue::Coinbase]);
+        assert_eq!(
+            prices(&merged.bids),
+            vec![
+                (Venue::Kraken, dec("100")),
+                (Venue::Coinbase, dec("100")),
+                (Venue::Kraken, dec("99")),
+                (Venue::Coinbase, dec("98")),
+            ]
+        );
+        assert_eq!(
+            prices(&merged.asks),
+            vec![(Venue::Kraken, dec("101")), (Venue::Coinbase, dec("102")), (Venue::Kraken, dec("103"))]
+        );
+        assert_eq!(merged.spread, Some(dec("1")));
+        assert_eq!(merged.arbitrage, None);
+    }
+
+    #[test]
+    fn reports_arbitrage_when_venues_cross() {
+        let kraken = book(Venue::Kraken, &[(dec("100"), dec("1"))], &[(dec("101"), dec("1"))]);
+        let coinbase = book(Venue::Coinbase, &[(dec("102"), dec("0.4"))], &[(dec("103"), dec("1"))]);
+
+        let merged = ConsolidatedBook::merge("XBT/USD", &[kraken, coinbase]).unwrap();
+
+        assert_eq!(merged.spread, Some(dec("-1")));
+        assert_eq!(
+            merged.arbitrage,
+            Some(Arbitrage {
+                buy_venue: Venue::Kraken,
+                sell_venue: Venue::Coinbase,
+                spread: dec("1"),
+                volume: dec("0.4"),
+            })
+        );
+    }
+
+    #[test]
+    fn no_books_no_consolidated_book() {
+        assert!(ConsolidatedBook::merge("XBT/USD", &[]).is_none());
+    }
+}
@@ -0,0 +1,363 @@
+//! Per-level differences between consecutive versions of a book
+//!
+//! The manager broadcasts a [`BookDelta`] for every book update instead of the
+//! whole book. Streaming clients start from one full snapshot and apply the
+//! deltas that follow it in `sequence` order.
+
+use crate::exchange::Venue;
+use crate::storage::{OrderbookSnapshot, PriceLevel};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// What happened to a price level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelAction {
+    Added,
+    /// Volume, order count or update time changed
+    Changed,
+    /// Gone from the book; the volume is zero
+    Removed,
+}
+
+/// Change to one price level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelChange {
+    pub action: LevelAction,
+    #[serde(flatten)]
+    pub level: PriceLevel,
+}
+
+/// Changes turning one version of a book into the next
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookDelta {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Position in the book's update stream; consecutive deltas differ by one
+    pub sequence: u64,
+    /// Set when the book was (re)created: drop any earlier levels before applying
+    #[serde(default)]
+    pub reset: bool,
+    /// Exchange time of the resulting book
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub received_at: Option<DateTime<Utc>>,
+    pub checksum: Option<u32>,
+    /// Feed sequence of the resulting book (see [`OrderbookSnapshot::sequence`])
+    pub book_sequence: Option<u64>,
+    pub bids: Vec<LevelChange>,
+    pub asks: Vec<LevelChange>,
+    #[serde(default)]
+    pub stale: bool,
+}
+
+impl BookDelta {
+    /// Changes turning `previous` into `current`; without a previous book every level is added
+    pub fn between(previous: Option<&OrderbookSnapshot>, current: &OrderbookSnapshot, sequence: u64) -> Self {
+        let (previous_bids, previous_asks) = previous.map_or((&[][..], &[][..]), |book| (&book.bids, &book.asks));
+
+        Self {
+            venue: current.venue,
+            symbol: current.symbol.clone(),
+            sequence,
+            reset: previous.is_none(),
+            timestamp: current.timestamp,
+            received_at: current.received_at,
+            checksum: current.checksum,
+            book_sequence: current.sequence,
+            bids: diff(previous_bids, &current.bids),
+            asks: diff(previous_asks, &current.asks),
+            stale: current.stale,
+        }
+    }
+
+    /// Apply to the book this delta was computed from, or to nothing for a reset
+    pub fn apply(&self, book: Option<OrderbookSnapshot>) -> OrderbookSnapshot {
+        let mut book = match book {
+            Some(book) if !self.reset => book,
+            _ => OrderbookSnapshot {
+                venue: self.venue,
+                symbol: self.symbol.clone(),
+                timestamp: self.timestamp,
+                received_at: None,
+                bids: Vec::new(),
+                asks: Vec::new(),
+                checksum: None,
+                sequence: None,
+                stale: false,
+            },
+        };
+
+        apply_side(&mut book.bids, &self.bids, |a, b| b.cmp(a));
+        apply_side(&mut book.asks, &self.asks, |a, b| a.cmp(b));
+        book.timestamp = self.timestamp;
+        book.received_at = self.received_at;
+        book.checksum = self.checksum;
+        book.sequence = self.book_sequence;
+        book.stale = self.stale;
+        book
+    }
+}
+
+/// What a [`DeltaStream`] makes of the next delta
+#[derive(Debug)]
+pub enum StreamUpdate {
+    /// Send this delta to the client
+    Send(BookDelta),
+    /// Already reflected in what the client has
+    Skip,
+    /// Deltas were missed; the stream must restart from a full book
+    Gap,
+}
+
+/// One client's view of a book's delta stream, optionally limited to the top `depth` levels.
+///
+/// Depth-limited streams mirror the full book so that levels moving into the
+/// top `depth` reach the client as additions.
+#[derive(Debug)]
+pub struct DeltaStream {
+    depth: Option<usize>,
+    /// Sequence of the last delta reflected in what the client has
+    sequence: u64,
+    /// Full book, kept for depth-limited streams only
+    book: Option<OrderbookSnapshot>,
+    /// Levels last sent to a depth-limited stream
+    sent: Option<OrderbookSnapshot>,
+}
+
+impl DeltaStream {
+    pub fn new(depth: Option<usize>) -> Self {
+        Self {
+            depth,
+            sequence: 0,
+            book: None,
+            sent: None,
+        }
+    }
+
+    /// Restart from a full book at `sequence`, returning the snapshot to send
+    pub fn restart(&mut self, snapshot: OrderbookSnapshot, sequence: u64) -> OrderbookSnapshot {
+        self.sequence = sequence;
+        match self.depth {
+            Some(depth) => {
+                let view = snapshot.truncated(depth);
+                self.book = Some(snapshot);
+                self.sent = Some(view.clone());
+                view
+            }
+            None => snapshot,
+        }
+    }
+
+    /// Follow the next delta of the book
+    pub fn next(&mut self, delta: BookDelta) -> StreamUpdate {
+        if !delta.reset {
+            if delta.sequence <= self.sequence {
+                return StreamUpdate::Skip;
+            }
+            if delta.sequence != self.sequence + 1 || (self.depth.is_some() && self.book.is_none()) {
+                return StreamUpdate::Gap;
+            }
+        }
+
+        let Some(depth) = self.depth else {
+            self.sequence = delta.sequence;
+            return StreamUpdate::Send(delta);
+        };
+        let book = delta.apply(self.book.take());
+        let view = book.truncated(depth);
+        let previous = if delta.reset { None } else { self.sent.as_ref() };
+        let limited = BookDelta::between(previous, &view, delta.sequence);
+
+        self.sequence = delta.sequence;
+        self.book = Some(book);
+        self.sent = Some(view);
+        StreamUpdate::Send(limited)
+    }
+}
+
+/// Level changes from `previous` to `current`, in the order of `current` followed by removals
+fn diff(previous: &[PriceLevel], current: &[PriceLevel]) -> Vec<LevelChange> {
+    let before: HashMap<Decimal, &PriceLevel> = previous.iter().map(|level| (level.price, level)).collect();
+    let after: HashMap<Decimal, &PriceLevel> = current.iter().map(|level| (level.price, level)).collect();
+
+    let updated = current.iter().filter_map(|level| {
+        let action = match before.get(&level.price) {
+            None => LevelAction::Added,
+            Some(&old) if old != level => LevelAction::Changed,
+            Some(_) => return None,
+        };
+        Some(LevelChange {
+            action,
+            level: level.clone(),
+        })
+    });
+    let removed = previous
+        .iter()
+        .filter(|level| !after.contains_key(&level.price))
+        .map(|level| LevelChange {
+            action: LevelAction::Removed,
+            level: PriceLevel {
+                price: level.price,
+                volume: Decimal::ZERO,
+                order_count: None,
+                timestamp: None,
+            },
+        });
+
+    updated.chain(removed).collect()
+}
+
+/// Apply level changes to one side, kept sorted best first by `order`
+fn apply_side(levels: &mut Vec<PriceLevel>, changes: &[LevelChange], order: impl Fn(&Decimal, &Decimal) -> Ordering) {
+    for change in changes {
+        let position = levels.binary_search_by(|level| order(&level.price, &change.level.price));
+        match (change.action, position) {
+            (LevelAction::Removed, Ok(index)) => {
+                levels.remove(index);
+            }
+            (LevelAction::Removed, Err(_)) => {}
+            (_, Ok(index)) => levels[index] = change.level.clone(),
+            (_, Err(index)) => levels.insert(index, change.level.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn book(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderbookSnapshot {
+        let levels = |levels: &[(&str, &str)]| {
+            levels
+                .iter()
+                .map(|&(price, volume)| PriceLevel {
+                    price: Decimal::from_str(price).unwrap(),
+                    volume: Decimal::from_str(volume).unwrap(),
+                    order_count: None,
+                    timestamp: None,
+                })
+                .collect()
+        };
+        OrderbookSnapshot {
+            venue: Venue::Kraken,
+            symbol: "XBT/USD".to_string(),
+            timestamp: Utc::now(),
+            received_at: None,
+            bids: levels(bids),
+            asks: levels(asks),
+            checksum: Some(42),
+            sequence: Some(7),
+            stale: false,
+        }
+    }
+
+    fn changes(changes: &[LevelChange]) -> Vec<(LevelAction, String, String)> {
+        changes
+            .iter()
+            .map(|change| (change.action, change.level.price.to_string(), change.level.volume.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diffs_added_changed_and_removed_levels() {
+        let previous = book(&[("100", "1"), ("99", "2")], &[("101", "1")]);
+        let current = book(&[("100", "1"), ("99", "3"), ("98", "1")], &[("102", "4")]);
+
+        let delta = BookDelta::between(Some(&previous), &current, 5);
+
+        assert_eq!(delta.sequence, 5);
+        assert!(!delta.reset);
+        assert_eq!(
+            changes(&delta.bids),
+            vec![
+                (LevelAction::Changed, "99".to_string(), "3".to_string()),
+                (LevelAction::Added, "98".to_string(), "1".to_string()),
+            ]
+        );
+        assert_eq!(
+            changes(&delta.asks),
+            vec![
+                (LevelAction::Added, "102".to_string(), "4".to_string()),
+                (LevelAction::Removed, "101".to_string(), "0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn applying_a_delta_reproduces_the_book() {
+        let previous = book(&[("100", "1"), ("99", "2"), ("97", "1")], &[("101", "1"), ("103", "2")]);
+        let mut current = book(&[("99.5", "1"), ("99", "2"), ("97", "5")], &[("101", "1"), ("102", "1")]);
+        current.stale = true;
+        current.sequence = Some(8);
+
+        let applied = BookDelta::between(Some(&previous), &current, 2).apply(Some(previous));
+
+        assert_eq!(applied.bids, current.bids);
+        assert_eq!(applied.asks, current.asks);
+        assert_eq!((applied.sequence, applied.stale), (Some(8), true));
+    }
+
+    #[test]
+    fn stream_skips_seen_deltas_and_detects_gaps() {
+        let first = book(&[("100", "1")], &[("101", "1")]);
+        let second = book(&[("100", "2")], &[("101", "1")]);
+        let third = book(&[("100", "3")], &[("101", "1")]);
+        let mut stream = DeltaStream::new(None);
+        stream.restart(second.clone(), 2);
+
+        let seen = BookDelta::between(Some(&first), &second, 2);
+        assert!(matches!(stream.next(seen), StreamUpdate::Skip));
+        let next = BookDelta::between(Some(&second), &third, 3);
+        assert!(matches!(stream.next(next), StreamUpdate::Send(delta) if delta.sequence == 3));
+        let skipped = BookDelta::between(Some(&third), &first, 5);
+        assert!(matches!(stream.next(skipped), StreamUpdate::Gap));
+        assert!(matches!(stream.next(BookDelta::between(None, &first, 1)), StreamUpdate::Send(delta) if delta.reset));
+    }
+
+    #[test]
+    fn depth_limited_stream_sends_changes_to_the_top_levels() {
+        let previous = book(&[("100", "1"), ("99", "1"), ("98", "1")], &[("101", "1")]);
+        let mut stream = DeltaStream::new(Some(2));
+        let sent = stream.restart(previous.clone(), 1);
+        assert_eq!(sent.bids.len(), 2);
+
+        // A change below the top two levels is not visible
+        let deeper = book(&[("100", "1"), ("99", "1"), ("98", "5")], &[("101", "1")]);
+        match stream.next(BookDelta::between(Some(&previous), &deeper, 2)) {
+            StreamUpdate::Send(delta) => assert!(delta.bids.is_empty()),
+            other => panic!("expected a delta, got {:?}", other),
+        }
+
+        // Removing the best bid pulls the third level into view
+        let current = book(&[("99", "1"), ("98", "5")], &[("101", "1")]);
+        match stream.next(BookDelta::between(Some(&deeper), &current, 3)) {
+            StreamUpdate::Send(delta) => assert_eq!(
+                changes(&delta.bids),
+                vec![
+                    (LevelAction::Added, "98".to_string(), "5".to_string()),
+                    (LevelAction::Removed, "100".to_string(), "0".to_string()),
+                ]
+            ),
+            other => panic!("expected a delta, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reset_delta_rebuilds_from_nothing() {
+        let stale = book(&[("90", "1")], &[("95", "1")]);
+        let current = book(&[("100", "1")], &[("101", "1")]);
+
+        let delta = BookDelta::between(None, &current, 1);
+        assert!(delta.reset);
+        assert!(delta.bids.iter().all(|change| change.action == LevelAction::Added));
+
+        let applied = delta.apply(Some(stale));
+        assert_eq!(applied.bids, current.bids);
+        assert_eq!(applied.asks, current.asks);
+    }
+}
@@ -0,0 +1,99 @@
+//! Exchange adapters feeding the shared market data pipeline
+//!
+//! An [`ExchangeAdapter`] speaks one venue's WebSocket protocol: it builds the
+//! frames that subscribe and unsubscribe pairs, keeps the local books for its
+//! connection and turns received frames into normalized [`FeedMessage`]s.
+//! Everything around it — connecting, sharding, reconnects, watchdogs and
+//! recording — is shared and lives in [`crate::feed`]. The Kraken adapters are
+//! in [`crate::kraken_client`].
+
+pub mod coinbase;
+
+use crate::feed::{BookSubscription, FeedConfig, FeedMessage};
+use crate::kraken_client::{v1, v2, ProtocolVersion};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Exchange a book or connection belongs to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Venue {
+    #[default]
+    Kraken,
+    /// Coinbase Advanced Trade
+    Coinbase,
+    /// Books implied from other books rather than received from an exchange (see [`crate::synthetic`])
+    Synthetic,
+}
+
+impl Venue {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Venue::Kraken => "kraken",
+            Venue::Coinbase => "coinbase",
+            Venue::Synthetic => "synthetic",
+        }
+    }
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Venue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "kraken" => Ok(Venue::Kraken),
+            "coinbase" => Ok(Venue::Coinbase),
+            "synthetic" => Ok(Venue::Synthetic),
+            other => Err(format!("Unknown venue: {}", other)),
+        }
+    }
+}
+
+/// Protocol-specific half of an exchange connection.
+///
+/// An adapter owns the local books for one connection and translates between
+/// wire frames and [`FeedMessage`]s; the socket handling is shared. Pairs are
+/// always named in canonical form (see [`crate::symbols::Symbol`]), whatever
+/// the venue calls them.
+pub trait ExchangeAdapter: Send {
+    /// Venue this adapter connects to
+    fn venue(&self) -> Venue;
+
+    /// Endpoint to connect to
+    fn url(&self) -> &'static str;
+
+    /// Frames that subscribe to the configured channels
+    fn subscribe_frames(&mut self, config: &FeedConfig) -> Vec<String>;
+
+    /// Parse a text frame received at `received_at` and apply it to the local books
+    fn handle_frame(&mut self, text: &str, received_at: DateTime<Utc>) -> Vec<FeedMessage>;
+
+    /// Frames that add one pair on every configured channel
+    fn subscribe_symbol(&mut self, sub: &BookSubscription, config: &FeedConfig) -> Vec<String>;
+
+    /// Drop the local book for `symbol` and return frames removing it from every configured channel
+    fn unsubscribe_symbol(&mut self, symbol: &str, config: &FeedConfig) -> Vec<String>;
+
+    /// Drop the local book for `symbol` and return frames requesting a fresh snapshot
+    fn resubscribe(&mut self, symbol: &str) -> Vec<String>;
+
+    /// Application-level ping, or `None` when the venue has none and a WebSocket ping is sent instead
+    fn ping_frame(&mut self) -> Option<String>;
+}
+
+/// Fresh adapter state for the configured venue and protocol version
+pub(crate) fn new_session(config: &FeedConfig) -> Result<Box<dyn ExchangeAdapter>, String> {
+    match (config.venue, config.protocol) {
+        (Venue::Kraken, ProtocolVersion::V1) => Ok(Box::new(v1::V1Session::new())),
+        (Venue::Kraken, ProtocolVersion::V2) => Ok(Box::new(v2::V2Session::new())),
+        (Venue::Coinbase, _) => Ok(Box::new(coinbase::CoinbaseSession::new())),
+        (venue, _) => Err(format!("{} books have no feed to connect to", venue)),
+    }
+}
@@ -0,0 +1,462 @@
+//! Coinbase Advanced Trade WebSocket adapter (`level2` channel)
+//!
+//! Coinbase names products with ISO codes and a dash (`BTC-USD`); they are
+//! translated to canonical pairs at the edge. The level2 channel streams the
+//! whole book without checksums, so books are kept unbounded and integrity is
+//! checked through `sequence_num`, which counts every message on the
+//! connection: a gap means messages were lost and every book is resubscribed.
+
+use crate::exchange::{ExchangeAdapter, Venue};
+use crate::feed::book::{BookError, OrderBook, PendingSnapshots, Side};
+use crate::feed::{
+    frame_excerpt, BookSubscription, FeedConfig, FeedMessage, SubscriptionStatus, DEFAULT_BOOK_DEPTH,
+};
+use crate::storage::PriceLevel;
+use crate::symbols::Symbol;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const URL: &str = "wss://advanced-trade-ws.coinbase.com";
+
+/// Coinbase product id (`BTC-USD`) for a canonical pair
+fn to_product_id(pair: &str) -> String {
+    Symbol::parse(pair)
+        .map(|symbol| symbol.v2_name().replace('/', "-"))
+        .unwrap_or_else(|_| pair.to_string())
+}
+
+/// Canonical pair (`XBT/USD`) for a Coinbase product id
+fn from_product_id(product_id: &str) -> String {
+    Symbol::parse(product_id)
+        .map(|symbol| symbol.v1_name())
+        .unwrap_or_else(|_| product_id.to_string())
+}
+
+/// Build the Coinbase feed configuration from environment variables, if `COINBASE_SYMBOLS` is set.
+///
+/// - `COINBASE_SYMBOLS`: comma separated `PAIR[:DEPTH]` list; depth is the number of levels kept per side
+/// - `COINBASE_WS_URL`: endpoint to connect to instead of the public one
+/// - `COINBASE_CONNECT_TIMEOUT_MS`, `COINBASE_PROXY`, `COINBASE_TLS_CA_FILE`, `COINBASE_TLS_INSECURE`,
+///   `COINBASE_RECONNECT_INITIAL_MS`, `COINBASE_RECONNECT_MAX_MS`, `COINBASE_IDLE_TIMEOUT_MS`,
+///   `COINBASE_SYMBOL_IDLE_TIMEOUT_MS`, `COINBASE_PING_INTERVAL_MS`, `COINBASE_SYMBOLS_PER_CONNECTION`:
+///   connection settings, with the same meaning and defaults as their `KRAKEN_*` counterparts
+///
+/// Only books are subscribed, and frames are neither recorded nor replayed.
+pub fn config_from_env() -> Result<Option<FeedConfig>, String> {
+    let Ok(value) = std::env::var("COINBASE_SYMBOLS") else {
+        return Ok(None);
+    };
+    let mut config = FeedConfig {
+        subscriptions: value
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| BookSubscription::parse(entry, DEFAULT_BOOK_DEPTH))
+            .collect::<Result<_, _>>()?,
+        venue: Venue::Coinbase,
+        trades: false,
+        ticker: false,
+        spread: false,
+        ohlc_interval: None,
+        ..FeedConfig::default()
+    };
+    config.apply_connection_env("COINBASE")?;
+    Ok(Some(config))
+}
+
+/// Subscribe/unsubscribe request
+#[derive(Debug, Serialize)]
+struct Request {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    product_ids: Vec<String>,
+    channel: &'static str,
+}
+
+/// Serialize a request into a frame
+fn request(kind: &'static str, channel: &'static str, product_ids: Vec<String>) -> String {
+    serde_json::to_string(&Request {
+        kind,
+        product_ids,
+        channel,
+    })
+    .expect("subscription request serializes")
+}
+
+/// Any frame received from Coinbase
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Frame {
+    Channel(ChannelFrame),
+    Error(ErrorFrame),
+}
+
+/// Channel message; `sequence_num` counts all messages on the connection
+#[derive(Debug, Deserialize)]
+struct ChannelFrame {
+    sequence_num: u64,
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    data: ChannelData,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "channel", content = "events", rename_all = "snake_case")]
+enum ChannelData {
+    L2Data(Vec<L2Event>),
+    Subscriptions(Vec<SubscriptionsEvent>),
+    Heartbeats(Vec<HeartbeatEvent>),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct HeartbeatEvent {
+    heartbeat_counter: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorFrame {
+    message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum L2EventKind {
+    Snapshot,
+    Update,
+}
+
+#[derive(Debug, Deserialize)]
+struct L2Event {
+    #[serde(rename = "type")]
+    kind: L2EventKind,
+    product_id: String,
+    updates: Vec<L2Update>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum L2Side {
+    Bid,
+    Offer,
+}
+
+/// New quantity at a price level; zero removes the level
+#[derive(Debug, Deserialize)]
+struct L2Update {
+    side: L2Side,
+    /// Epoch for snapshot levels, which carry no update time
+    event_time: DateTime<Utc>,
+    price_level: Decimal,
+    new_quantity: Decimal,
+}
+
+/// Current subscriptions of the connection, keyed by channel
+#[derive(Debug, Deserialize)]
+struct SubscriptionsEvent {
+    subscriptions: HashMap<String, Vec<String>>,
+}
+
+/// Coinbase protocol state for one connection
+pub(crate) struct CoinbaseSession {
+    /// Local books keyed by canonical pair
+    orderbooks: HashMap<String, OrderBook>,
+    /// Levels kept in snapshots per pair
+    depths: HashMap<String, usize>,
+    pending: PendingSnapshots,
+    /// `sequence_num` of the last message received
+    last_sequence: Option<u64>,
+}
+
+impl CoinbaseSession {
+    pub(crate) fn new() -> Self {
+        Self {
+            orderbooks: HashMap::new(),
+            depths: HashMap::new(),
+            pending: PendingSnapshots::default(),
+            last_sequence: None,
+        }
+    }
+
+    /// Drop every book if `sequence` skipped ahead, reporting each for resubscription
+    fn check_sequence(&mut self, sequence: u64) -> Vec<FeedMessage> {
+        let expected = self.last_sequence.map(|last| last + 1);
+        self.last_sequence = Some(sequence);
+        let Some(expected) = expected.filter(|expected| sequence > *expected) else {
+            return Vec::new();
+        };
+
+        tracing::warn!("Coinbase sequence gap (expected {}, received {})", expected, sequence);
+        self.orderbooks
+            .drain()
+            .map(|(symbol, _)| {
+                // Updates for the dropped books are ignored until the fresh snapshot
+                self.pending.expect(&symbol);
+                FeedMessage::InvalidBook {
+                    symbol,
+                    reason: BookError::SequenceGap {
+                        expected,
+                        received: sequence,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Apply a level2 event; a snapshot starts a new book and updates are refused until one was received
+    fn apply_event(&mut self, event: L2Event, exchange_time: DateTime<Utc>, received_at: DateTime<Utc>) -> FeedMessage {
+        let pair = from_product_id(&event.product_id);
+        if event.kind == L2EventKind::Snapshot {
+            let depth = self.depths.get(&pair).copied().unwrap_or(DEFAULT_BOOK_DEPTH);
+            self.orderbooks
+                .insert(pair.clone(), OrderBook::unbounded(Venue::Coinbase, depth));
+        }
+        let Some(book) = self.orderbooks.get_mut(&pair) else {
+            return FeedMessage::InvalidBook {
+                symbol: pair,
+                reason: BookError::MissingSnapshot,
+            };
+        };
+        if event.kind == L2EventKind::Update {
+            book.advance();
+        }
+
+        for update in event.updates {
+            let side = match update.side {
+                L2Side::Bid => Side::Bid,
+                L2Side::Offer => Side::Ask,
+            };
+            let level = PriceLevel {
+                price: update.price_level,
+                volume: update.new_quantity,
+                order_count: None,
+                timestamp: (update.event_time > DateTime::UNIX_EPOCH).then_some(update.event_time),
+            };
+            book.apply(side, level);
+        }
+
+        if let Err(reason) = book.validate() {
+            return FeedMessage::InvalidBook { symbol: pair, reason };
+        }
+
+        FeedMessage::Snapshot(book.to_snapshot(&pair, Some(exchange_time), received_at, None))
+    }
+}
+
+impl ExchangeAdapter for CoinbaseSession {
+    fn venue(&self) -> Venue {
+        Venue::Coinbase
+    }
+
+    fn url(&self) -> &'static str {
+        URL
+    }
+
+    fn subscribe_frames(&mut self, config: &FeedConfig) -> Vec<String> {
+        if config.subscriptions.is_empty() {
+            return Vec::new();
+        }
+
+        let mut product_ids = Vec::new();
+        for sub in &config.subscriptions {
+            self.depths.insert(sub.pair.to_string(), sub.depth);
+            self.pending.expect(&sub.pair.to_string());
+            product_ids.push(to_product_id(&sub.pair.to_string()));
+        }
+
+        // Heartbeats keep the connection busy (and the sequence moving) when books are quiet
+        vec![
+            request("subscribe", "level2", product_ids),
+            request("subscribe", "heartbeats", Vec::new()),
+        ]
+    }
+
+    fn handle_frame(&mut self, text: &str, received_at: DateTime<Utc>) -> Vec<FeedMessage> {
+        let frame = match serde_json::from_str(text) {
+            Ok(Frame::Channel(frame)) => frame,
+            Ok(Frame::Error(error)) => {
+                tracing::warn!("Coinbase error: {}", error.message);
+                return Vec::new();
+            }
+            Err(e) => {
+                tracing::warn!("Unrecognized Coinbase frame ({}): {}", e, frame_excerpt(text));
+                return Vec::new();
+            }
+        };
+
+        let mut messages = self.check_sequence(frame.sequence_num);
+        match frame.data {
+            ChannelData::L2Data(events) => {
+                for event in events {
+                    let message = self.apply_event(event, frame.timestamp, received_at);
+                    if self.pending.admit(&message) {
+                        messages.push(message);
+                    }
+                }
+            }
+            ChannelData::Subscriptions(events) => {
+                let products = events
+                    .into_iter()
+                    .filter_map(|mut event| event.subscriptions.remove("level2"))
+                    .flatten();
+                messages.extend(products.map(|product_id| FeedMessage::SubscriptionStatus {
+                    symbol: from_product_id(&product_id),
+                    status: SubscriptionStatus::Subscribed,
+                }));
+            }
+            ChannelData::Heartbeats(events) => {
+                if let Some(event) = events.last() {
+                    tracing::trace!("Coinbase heartbeat {}", event.heartbeat_counter);
+                }
+                messages.push(FeedMessage::Heartbeat);
+            }
+            ChannelData::Other => {}
+        }
+        messages
+    }
+
+    fn subscribe_symbol(&mut self, sub: &BookSubscription, _config: &FeedConfig) -> Vec<String> {
+        self.depths.insert(sub.pair.to_string(), sub.depth);
+        self.pending.expect(&sub.pair.to_string());
+        vec![request("subscribe", "level2", vec![to_product_id(&sub.pair.to_string())])]
+    }
+
+    fn unsubscribe_symbol(&mut self, symbol: &str, _config: &FeedConfig) -> Vec<String> {
+        self.orderbooks.remove(symbol);
+        self.pending.forget(symbol);
+        self.depths.remove(symbol);
+        vec![request("unsubscribe", "level2", vec![to_product_id(symbol)])]
+    }
+
+    fn resubscribe(&mut self, symbol: &str) -> Vec<String> {
+        self.orderbooks.remove(symbol);
+        self.pending.expect(symbol);
+
+        ["unsubscribe", "subscribe"]
+            .into_iter()
+            .map(|kind| request(kind, "level2", vec![to_product_id(symbol)]))
+            .collect()
+    }
+
+    fn ping_frame(&mut self) -> Option<String> {
+        // Coinbase has no application-level ping
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUBSCRIPTIONS: &str = include_str!("../../tests/fixtures/coinbase/subscriptions.json");
+    const L2_SNAPSHOT: &str = include_str!("../../tests/fixtures/coinbase/l2_snapshot.json");
+    const L2_UPDATE: &str = include_str!("../../tests/fixtures/coinbase/l2_update.json");
+    const HEARTBEAT: &str = include_str!("../../tests/fixtures/coinbase/heartbeat.json");
+    const L2_UPDATE_AFTER_GAP: &str = include_str!("../../tests/fixtures/coinbase/l2_update_after_gap.json");
+
+    fn subscribed_session() -> CoinbaseSession {
+        let mut session = CoinbaseSession::new();
+        session.subscribe_frames(&FeedConfig {
+            subscriptions: vec![BookSubscription::new("XBT/USD", 10).unwrap()],
+            venue: Venue::Coinbase,
+            ..Default::default()
+        });
+        session
+    }
+
+    /// Parse a text frame received just now
+    fn handle_text(session: &mut impl ExchangeAdapter, text: &str) -> Vec<FeedMessage> {
+        session.handle_frame(text, Utc::now())
+    }
+
+    fn expect_snapshot(mut messages: Vec<FeedMessage>) -> crate::storage::OrderbookSnapshot {
+        assert_eq!(messages.len(), 1);
+        match messages.remove(0) {
+            FeedMessage::Snapshot(snapshot) => snapshot,
+            other => panic!("expected a book snapshot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn subscribes_with_product_ids() {
+        let mut session = CoinbaseSession::new();
+        let frames = session.subscribe_frames(&FeedConfig {
+            subscriptions: vec![
+                BookSubscription::new("XBT/USD", 10).unwrap(),
+                BookSubscription::new("ETH/USD", 25).unwrap(),
+            ],
+            ..Default::default()
+        });
+
+        assert_eq!(frames.len(), 2);
+        let level2: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(level2["type"], "subscribe");
+        assert_eq!(level2["channel"], "level2");
+        assert_eq!(level2["product_ids"], serde_json::json!(["BTC-USD", "ETH-USD"]));
+        assert_eq!(frames[1], r#"{"type":"subscribe","channel":"heartbeats"}"#);
+    }
+
+    #[test]
+    fn reports_subscriptions_and_heartbeats() {
+        let mut session = subscribed_session();
+        assert!(matches!(
+            handle_text(&mut session, SUBSCRIPTIONS).as_slice(),
+            [FeedMessage::SubscriptionStatus { symbol, status: SubscriptionStatus::Subscribed }] if symbol == "XBT/USD"
+        ));
+        expect_snapshot(handle_text(&mut session, L2_SNAPSHOT));
+        expect_snapshot(handle_text(&mut session, L2_UPDATE));
+        assert!(matches!(handle_text(&mut session, HEARTBEAT).as_slice(), [FeedMessage::Heartbeat]));
+    }
+
+    #[test]
+    fn applies_level2_snapshot_and_update() {
+        let mut session = subscribed_session();
+        let snapshot = expect_snapshot(handle_text(&mut session, L2_SNAPSHOT));
+
+        assert_eq!(snapshot.venue, Venue::Coinbase);
+        assert_eq!(snapshot.symbol, "XBT/USD");
+        assert_eq!(snapshot.sequence, Some(0));
+        assert_eq!(snapshot.bids.len(), 3);
+        assert_eq!(snapshot.bids[0].price.to_string(), "21921.73");
+        assert_eq!(snapshot.asks[0].price.to_string(), "21921.74");
+        assert_eq!(snapshot.bids[0].timestamp, None, "snapshot levels carry no update time");
+        assert_eq!(snapshot.checksum, None);
+
+        let snapshot = expect_snapshot(handle_text(&mut session, L2_UPDATE));
+        assert_eq!(snapshot.sequence, Some(1));
+        assert_eq!(snapshot.bids[0].price.to_string(), "21921.3");
+        assert_eq!(snapshot.asks[0].price.to_string(), "21921.74");
+        assert_eq!(snapshot.asks[1].price.to_string(), "21921.9");
+        assert_eq!(snapshot.asks[1].volume.to_string(), "0.35");
+        assert_eq!(
+            snapshot.timestamp,
+            "2023-02-09T20:32:51.102536785Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn refuses_updates_before_snapshot() {
+        let mut session = subscribed_session();
+        assert!(handle_text(&mut session, L2_UPDATE).is_empty());
+    }
+
+    #[test]
+    fn drops_books_after_sequence_gap() {
+        let mut session = subscribed_session();
+        expect_snapshot(handle_text(&mut session, L2_SNAPSHOT));
+        expect_snapshot(handle_text(&mut session, L2_UPDATE));
+
+        assert!(matches!(
+            handle_text(&mut session, L2_UPDATE_AFTER_GAP).as_slice(),
+            [FeedMessage::InvalidBook { symbol, reason: BookError::SequenceGap { expected: 3, received: 7 } }]
+                if symbol == "XBT/USD"
+        ));
+        let frames = session.resubscribe("XBT/USD");
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].contains(r#""type":"unsubscribe""#));
+        assert!(frames[1].contains(r#""product_ids":["BTC-USD"]"#));
+    }
+}
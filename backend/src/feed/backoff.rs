@@ -0,0 +1,47 @@
+//! Jittered exponential backoff for reconnect attempts
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Exponential backoff with jitter: each delay is drawn uniformly from the
+/// upper half of `min(max, initial * 2^attempt)`, so clients reconnecting
+/// after the same outage do not hit the server in lockstep.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            attempt: 0,
+        }
+    }
+
+    /// Delay before the next attempt
+    pub fn next_delay(&mut self) -> Duration {
+        let ceiling = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        ceiling.mul_f64(0.5 + 0.5 * random_fraction())
+    }
+
+    /// Start over from the initial delay after a healthy connection
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Uniform value in `[0, 1)` from the randomly seeded std hasher
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
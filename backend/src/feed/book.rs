@@ -0,0 +1,377 @@
+//! Ordered price-level book maintained by the Kraken client
+
+use crate::exchange::Venue;
+use crate::feed::FeedMessage;
+use crate::storage::{OrderbookSnapshot, PriceLevel};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Side of the book a level belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// Reason a local book can no longer be trusted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookError {
+    /// An update arrived for a pair whose snapshot was never received
+    MissingSnapshot,
+    /// Best bid at or above best ask
+    Crossed { bid: Decimal, ask: Decimal },
+    /// A level with a zero or negative price
+    InvalidPrice(Decimal),
+    /// Messages were lost on the connection, so every book on it may be incomplete
+    SequenceGap { expected: u64, received: u64 },
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::MissingSnapshot => write!(f, "update received before the snapshot"),
+            BookError::Crossed { bid, ask } => write!(f, "crossed book (best bid {} >= best ask {})", bid, ask),
+            BookError::InvalidPrice(price) => write!(f, "invalid price level {}", price),
+            BookError::SequenceGap { expected, received } => {
+                write!(f, "sequence gap (expected {}, received {})", expected, received)
+            }
+        }
+    }
+}
+
+/// Local copy of an exchange book, keyed by price on each side.
+///
+/// Inserts and removals are O(log n) and the best levels are read straight
+/// off the ends of the maps, so nothing is re-sorted or cloned per update.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    bids: BTreeMap<Decimal, PriceLevel>,
+    asks: BTreeMap<Decimal, PriceLevel>,
+    depth: usize,
+    /// Keep levels beyond `depth`, for venues that send the whole book
+    unbounded: bool,
+    venue: Venue,
+    /// Update messages applied since the snapshot
+    sequence: u64,
+}
+
+impl OrderBook {
+    /// Create an empty Kraken book that keeps at most `depth` levels per side
+    pub fn new(depth: usize) -> Self {
+        Self {
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            depth,
+            unbounded: false,
+            venue: Venue::Kraken,
+            sequence: 0,
+        }
+    }
+
+    /// Create an empty book for a venue that streams every level of the book.
+    ///
+    /// Such venues do not resend a level that moves into the top `depth` once a
+    /// better one is removed, so all levels are kept and only snapshots are
+    /// limited to `depth`.
+    pub fn unbounded(venue: Venue, depth: usize) -> Self {
+        Self {
+            unbounded: true,
+            venue,
+            ..Self::new(depth)
+        }
+    }
+
+    /// Replace one side of the book with snapshot levels
+    pub fn replace_side(&mut self, side: Side, levels: Vec<PriceLevel>) {
+        let book_side = self.side_mut(side);
+        book_side.clear();
+        for level in levels {
+            if level.volume > Decimal::ZERO {
+                book_side.insert(level.price, level);
+            }
+        }
+        self.truncate(side);
+    }
+
+    /// Apply a single level update; zero volume removes the level
+    pub fn apply(&mut self, side: Side, level: PriceLevel) {
+        let book_side = self.side_mut(side);
+        if level.volume.is_zero() {
+            book_side.remove(&level.price);
+        } else {
+            book_side.insert(level.price, level);
+        }
+        self.truncate(side);
+    }
+
+    /// Best bids, highest price first
+    pub fn top_bids(&self, n: usize) -> impl Iterator<Item = &PriceLevel> {
+        self.bids.values().rev().take(n)
+    }
+
+    /// Best asks, lowest price first
+    pub fn top_asks(&self, n: usize) -> impl Iterator<Item = &PriceLevel> {
+        self.asks.values().take(n)
+    }
+
+    /// Maximum number of levels kept per side
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Number of levels currently held on each side as (bids, asks)
+    pub fn len(&self) -> (usize, usize) {
+        (self.bids.len(), self.asks.len())
+    }
+
+    /// Whether both sides of the book are empty
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /// Count one applied update message
+    pub fn advance(&mut self) {
+        self.sequence += 1;
+    }
+
+    /// Update messages applied since the snapshot (0 for the snapshot itself)
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Check that the book is uncrossed and all prices are positive
+    pub fn validate(&self) -> Result<(), BookError> {
+        let lowest_bid = self.bids.keys().next();
+        let lowest_ask = self.asks.keys().next();
+        if let Some(&price) = lowest_bid.into_iter().chain(lowest_ask).find(|p| **p <= Decimal::ZERO) {
+            return Err(BookError::InvalidPrice(price));
+        }
+        if let (Some(&bid), Some(&ask)) = (self.bids.keys().next_back(), lowest_ask) {
+            if bid >= ask {
+                return Err(BookError::Crossed { bid, ask });
+            }
+        }
+        Ok(())
+    }
+
+    /// Compute the Kraken book checksum over the top 10 levels of each side.
+    ///
+    /// For each ask (lowest first) then each bid (highest first), the price and
+    /// volume are printed as sent by Kraken with the decimal point and leading
+    /// zeros removed, concatenated, and hashed with CRC32.
+    pub fn checksum(&self) -> u32 {
+        let mut payload = String::new();
+        for level in self.top_asks(10).chain(self.top_bids(10)) {
+            payload.push_str(&checksum_digits(&level.price));
+            payload.push_str(&checksum_digits(&level.volume));
+        }
+        crc32fast::hash(payload.as_bytes())
+    }
+
+    /// Materialize the book as a snapshot, attaching an already verified checksum.
+    ///
+    /// The snapshot is stamped with the exchange time of the most recent
+    /// level update, falling back to `received_at` when the exchange sent none.
+    pub fn to_snapshot(
+        &self,
+        symbol: &str,
+        exchange_time: Option<DateTime<Utc>>,
+        received_at: DateTime<Utc>,
+        checksum: Option<u32>,
+    ) -> OrderbookSnapshot {
+        OrderbookSnapshot {
+            venue: self.venue,
+            symbol: symbol.to_string(),
+            timestamp: exchange_time.unwrap_or(received_at),
+            received_at: Some(received_at),
+            bids: self.top_bids(self.depth).cloned().collect(),
+            asks: self.top_asks(self.depth).cloned().collect(),
+            checksum,
+            sequence: Some(self.sequence),
+            stale: false,
+        }
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut BTreeMap<Decimal, PriceLevel> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+
+    /// Drop levels that fell outside the subscribed depth
+    fn truncate(&mut self, side: Side) {
+        if self.unbounded {
+            return;
+        }
+        match side {
+            Side::Bid => {
+                while self.bids.len() > self.depth {
+                    self.bids.pop_first();
+                }
+            }
+            Side::Ask => {
+                while self.asks.len() > self.depth {
+                    self.asks.pop_last();
+                }
+            }
+        }
+    }
+}
+
+/// Pairs whose book was (re)requested and is waiting for its snapshot.
+///
+/// Updates still in flight from a previous subscription are dropped instead of
+/// being reported as a missing snapshot, which would resubscribe again.
+#[derive(Debug, Default)]
+pub struct PendingSnapshots(HashSet<String>);
+
+impl PendingSnapshots {
+    /// Expect a fresh snapshot for `symbol`
+    pub fn expect(&mut self, symbol: &str) {
+        self.0.insert(symbol.to_string());
+    }
+
+    /// Stop waiting for `symbol`, e.g. after unsubscribing
+    pub fn forget(&mut self, symbol: &str) {
+        self.0.remove(symbol);
+    }
+
+    /// Whether `message` should be passed on; clears the pair once its snapshot arrives
+    pub fn admit(&mut self, message: &FeedMessage) -> bool {
+        match message {
+            FeedMessage::Snapshot(snapshot) if snapshot.sequence == Some(0) => {
+                self.0.remove(&snapshot.symbol);
+                true
+            }
+            FeedMessage::InvalidBook {
+                symbol,
+                reason: BookError::MissingSnapshot,
+            } if self.0.contains(symbol) => {
+                tracing::debug!("Dropping {} update received before its snapshot", symbol);
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Format a decimal for checksumming: drop the decimal point and leading zeros
+fn checksum_digits(value: &Decimal) -> String {
+    let digits: String = value.to_string().chars().filter(|c| *c != '.').collect();
+    digits.trim_start_matches('0').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn level(price: &str, volume: &str) -> PriceLevel {
+        PriceLevel {
+            price: Decimal::from_str(price).unwrap(),
+            volume: Decimal::from_str(volume).unwrap(),
+            order_count: None,
+            timestamp: None,
+        }
+    }
+
+    fn prices<'a>(levels: impl Iterator<Item = &'a PriceLevel>) -> Vec<String> {
+        levels.map(|level| level.price.to_string()).collect()
+    }
+
+    fn snapshot(symbol: &str, sequence: u64) -> FeedMessage {
+        let mut snapshot = OrderBook::new(10).to_snapshot(symbol, None, Utc::now(), None);
+        snapshot.sequence = Some(sequence);
+        FeedMessage::Snapshot(snapshot)
+    }
+
+    #[test]
+    fn keeps_the_best_levels_within_depth() {
+        let mut book = OrderBook::new(2);
+        book.replace_side(Side::Bid, vec![level("99", "1"), level("100", "0"), level("98", "1"), level("97", "1")]);
+        book.replace_side(Side::Ask, vec![level("103", "1"), level("101", "1"), level("102", "1")]);
+        assert_eq!(prices(book.top_bids(10)), ["99", "98"], "zero volume is dropped, then the worst bids");
+        assert_eq!(prices(book.top_asks(10)), ["101", "102"]);
+
+        // A better level pushes the worst one out
+        book.apply(Side::Bid, level("99.5", "2"));
+        book.apply(Side::Ask, level("100.5", "2"));
+        assert_eq!(prices(book.top_bids(10)), ["99.5", "99"]);
+        assert_eq!(prices(book.top_asks(10)), ["100.5", "101"]);
+
+        // Zero volume removes a level; the one pushed out does not come back
+        book.apply(Side::Ask, level("100.5", "0"));
+        book.apply(Side::Bid, level("42", "0"));
+        assert_eq!(prices(book.top_asks(10)), ["101"]);
+        assert_eq!(book.len(), (2, 1));
+    }
+
+    #[test]
+    fn unbounded_books_keep_levels_beyond_depth() {
+        let mut book = OrderBook::unbounded(Venue::Coinbase, 1);
+        book.replace_side(Side::Ask, vec![level("101", "1"), level("102", "1")]);
+        book.apply(Side::Ask, level("101", "0"));
+
+        let snapshot = book.to_snapshot("XBT/USD", None, Utc::now(), None);
+        assert_eq!(snapshot.venue, Venue::Coinbase);
+        assert_eq!(prices(snapshot.asks.iter()), ["102"], "the next level moves into depth");
+    }
+
+    #[test]
+    fn checksum_matches_kraken_example() {
+        // Book from Kraken's v1 checksum documentation, levels as sent on the wire
+        let asks = ["0.05005", "0.05010", "0.05015", "0.05020", "0.05025", "0.05030", "0.05035", "0.05040", "0.05045", "0.05050"];
+        let bids = ["0.05000", "0.04995", "0.04990", "0.04980", "0.04975", "0.04970", "0.04965", "0.04960", "0.04955", "0.04950"];
+        let mut book = OrderBook::new(10);
+        book.replace_side(Side::Ask, asks.iter().map(|price| level(price, "0.00000500")).collect());
+        book.replace_side(Side::Bid, bids.iter().map(|price| level(price, "0.00000500")).collect());
+
+        assert_eq!(book.checksum(), 974947235);
+    }
+
+    #[test]
+    fn validates_prices_and_crossing() {
+        let mut book = OrderBook::new(10);
+        book.replace_side(Side::Bid, vec![level("100", "1")]);
+        book.replace_side(Side::Ask, vec![level("101", "1")]);
+        assert_eq!(book.validate(), Ok(()));
+
+        book.apply(Side::Bid, level("101", "1"));
+        assert_eq!(
+            book.validate(),
+            Err(BookError::Crossed {
+                bid: Decimal::from(101),
+                ask: Decimal::from(101)
+            })
+        );
+
+        book.apply(Side::Bid, level("101", "0"));
+        book.apply(Side::Bid, level("-1", "1"));
+        assert_eq!(book.validate(), Err(BookError::InvalidPrice(Decimal::from(-1))));
+    }
+
+    #[test]
+    fn pending_snapshots_drop_stale_updates_until_the_snapshot() {
+        let missing = |symbol: &str| FeedMessage::InvalidBook {
+            symbol: symbol.to_string(),
+            reason: BookError::MissingSnapshot,
+        };
+        let mut pending = PendingSnapshots::default();
+        pending.expect("XBT/USD");
+
+        assert!(!pending.admit(&missing("XBT/USD")), "leftover update of the old subscription");
+        assert!(pending.admit(&missing("ETH/USD")), "not waiting for this pair");
+        assert!(pending.admit(&snapshot("XBT/USD", 1)));
+        assert!(!pending.admit(&missing("XBT/USD")), "only a snapshot ends the wait");
+
+        assert!(pending.admit(&snapshot("XBT/USD", 0)));
+        assert!(pending.admit(&missing("XBT/USD")), "an update without a book is an error again");
+
+        pending.expect("ETH/USD");
+        pending.forget("ETH/USD");
+        assert!(pending.admit(&missing("ETH/USD")));
+    }
+}
@@ -0,0 +1,119 @@
+//! Runtime control of the symbols tracked by a running feed connection
+//!
+//! The subscription set is shared between the [`FeedControl`] handle and the
+//! connection, so changes made while disconnected are picked up when the next
+//! connection subscribes.
+
+use crate::feed::BookSubscription;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Change to the live subscription set
+#[derive(Debug, Clone)]
+pub enum FeedCommand {
+    Subscribe(BookSubscription),
+    Unsubscribe(String),
+}
+
+/// Handle for adding and removing symbols at runtime
+#[derive(Clone)]
+pub struct FeedControl {
+    subscriptions: Arc<Mutex<Vec<BookSubscription>>>,
+    commands: mpsc::UnboundedSender<FeedCommand>,
+}
+
+/// Connection side of a [`FeedControl`]
+pub struct FeedCommands {
+    subscriptions: Arc<Mutex<Vec<BookSubscription>>>,
+    commands: mpsc::UnboundedReceiver<FeedCommand>,
+}
+
+/// Create a control handle and the command stream for the connection loop
+pub fn feed_control(initial: Vec<BookSubscription>) -> (FeedControl, FeedCommands) {
+    let subscriptions = Arc::new(Mutex::new(initial));
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    (
+        FeedControl {
+            subscriptions: subscriptions.clone(),
+            commands: tx,
+        },
+        FeedCommands {
+            subscriptions,
+            commands: rx,
+        },
+    )
+}
+
+impl FeedControl {
+    /// Currently tracked subscriptions
+    pub fn subscriptions(&self) -> Vec<BookSubscription> {
+        self.subscriptions.lock().unwrap().clone()
+    }
+
+    /// Start tracking a pair, or change the depth of a tracked one
+    pub fn subscribe(&self, sub: BookSubscription) -> Result<(), String> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        match subscriptions.iter_mut().find(|s| s.pair == sub.pair) {
+            Some(existing) if existing.depth == sub.depth => {
+                return Err(format!("{} is already subscribed at depth {}", sub.pair, sub.depth));
+            }
+            Some(existing) => {
+                // Exchanges keep one book subscription per pair; drop the old depth first
+                *existing = sub.clone();
+                self.send(FeedCommand::Unsubscribe(sub.pair.to_string()))?;
+            }
+            None => subscriptions.push(sub.clone()),
+        }
+
+        self.send(FeedCommand::Subscribe(sub))
+    }
+
+    /// Stop tracking a pair
+    pub fn unsubscribe(&self, pair: &str) -> Result<(), String> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        let before = subscriptions.len();
+        subscriptions.retain(|s| s.pair != pair);
+        if subscriptions.len() == before {
+            return Err(format!("{} is not subscribed", pair));
+        }
+
+        self.send(FeedCommand::Unsubscribe(pair.to_string()))
+    }
+
+    fn send(&self, command: FeedCommand) -> Result<(), String> {
+        self.commands
+            .send(command)
+            .map_err(|_| "Feed is not running".to_string())
+    }
+}
+
+impl FeedCommands {
+    /// Subscriptions to request when (re)connecting.
+    ///
+    /// Commands queued while disconnected are already reflected in the
+    /// returned set, so they are discarded rather than replayed twice.
+    pub fn take_subscriptions(&mut self) -> Vec<BookSubscription> {
+        // Hold the lock so no command can slip in between draining and reading
+        let subscriptions = self.subscriptions.lock().unwrap();
+        while self.commands.try_recv().is_ok() {}
+        subscriptions.clone()
+    }
+
+    /// Pairs currently tracked on this connection
+    pub fn pairs(&self) -> Vec<String> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|sub| sub.pair.to_string())
+            .collect()
+    }
+
+    /// Wait for the next command
+    pub async fn recv(&mut self) -> Option<FeedCommand> {
+        self.commands.recv().await
+    }
+}
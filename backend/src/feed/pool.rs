@@ -0,0 +1,116 @@
+//! Pool of exchange connections with the tracked pairs sharded across them
+//!
+//! Each connection has its own subscription set and reconnect loop. A pair is
+//! placed on the first connection with room, and another connection is opened
+//! once every existing one carries [`FeedConfig::symbols_per_connection`]
+//! pairs. Connections are never closed; one left without pairs is reused for
+//! the next subscription.
+
+use crate::feed::{
+    feed_control, run_connection, BookSubscription, FeedConfig, FeedControl, FrameRecorder, OrderbookCallback,
+};
+use std::sync::Arc;
+
+pub(super) struct ConnectionPool {
+    callback: Arc<dyn OrderbookCallback>,
+    config: FeedConfig,
+    recorder: Option<Arc<FrameRecorder>>,
+    /// Subscription handle of each connection, indexed by connection id
+    connections: Vec<FeedControl>,
+}
+
+impl ConnectionPool {
+    pub(super) fn new(
+        callback: Arc<dyn OrderbookCallback>,
+        config: FeedConfig,
+        recorder: Option<Arc<FrameRecorder>>,
+    ) -> Self {
+        Self {
+            callback,
+            config,
+            recorder,
+            connections: Vec::new(),
+        }
+    }
+
+    /// Track a pair on the connection that already carries it, or on one with room
+    pub(super) fn subscribe(&mut self, sub: BookSubscription) {
+        let pair = sub.pair.to_string();
+        let connection = match self.owner(&pair) {
+            Some(connection) => connection,
+            None => self.connection_with_room(),
+        };
+        tracing::info!("Assigning {} to {} connection {}", pair, self.config.venue, connection);
+        if let Err(e) = self.connections[connection].subscribe(sub) {
+            self.callback.on_error(format!("Failed to subscribe {}: {}", pair, e));
+        }
+    }
+
+    /// Stop tracking a pair on the connection that carries it
+    pub(super) fn unsubscribe(&mut self, pair: &str) {
+        let Some(connection) = self.owner(pair) else {
+            tracing::warn!("{} is not assigned to any {} connection", pair, self.config.venue);
+            return;
+        };
+        if let Err(e) = self.connections[connection].unsubscribe(pair) {
+            self.callback.on_error(format!("Failed to unsubscribe {}: {}", pair, e));
+        }
+    }
+
+    /// Connection currently carrying `pair`
+    fn owner(&self, pair: &str) -> Option<usize> {
+        self.connections
+            .iter()
+            .position(|control| control.subscriptions().iter().any(|s| s.pair == pair))
+    }
+
+    /// First connection below the per-connection limit, opening a new one if all are full
+    fn connection_with_room(&mut self) -> usize {
+        let loads: Vec<usize> = self
+            .connections
+            .iter()
+            .map(|control| control.subscriptions().len())
+            .collect();
+        match first_with_room(&loads, self.config.symbols_per_connection) {
+            Some(connection) => connection,
+            None => self.open_connection(),
+        }
+    }
+
+    fn open_connection(&mut self) -> usize {
+        let connection = self.connections.len();
+        tracing::info!("Opening {} connection {}", self.config.venue, connection);
+
+        let (control, commands) = feed_control(Vec::new());
+        tokio::spawn(run_connection(
+            connection,
+            self.callback.clone(),
+            self.config.clone(),
+            commands,
+            self.recorder.clone(),
+        ));
+        self.connections.push(control);
+        connection
+    }
+}
+
+/// Index of the first connection carrying fewer than `limit` pairs
+fn first_with_room(loads: &[usize], limit: Option<usize>) -> Option<usize> {
+    let limit = limit.unwrap_or(usize::MAX);
+    loads.iter().position(|&load| load < limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_connections_in_order() {
+        assert_eq!(first_with_room(&[], Some(2)), None);
+        assert_eq!(first_with_room(&[2, 1], Some(2)), Some(1));
+        assert_eq!(first_with_room(&[2, 2], Some(2)), None);
+        // A connection emptied by unsubscribes is filled again first
+        assert_eq!(first_with_room(&[2, 0, 1], Some(2)), Some(1));
+        assert_eq!(first_with_room(&[500], None), Some(0));
+    }
+}
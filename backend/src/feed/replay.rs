@@ -0,0 +1,271 @@
+//! Replay of recorded Kraken frames
+//!
+//! Drives an [`OrderbookCallback`] from files written by the
+//! [`recorder`](super::recorder) instead of a live connection, so the whole
+//! backend can run offline. Frames go through the same protocol session as a
+//! live feed and keep their recorded receive times, so a replay always
+//! produces the same snapshots.
+
+use crate::exchange::new_session;
+use crate::feed::recorder::{RecordedFrame, RECORDING_EXTENSION};
+use crate::feed::{dispatch, FeedConfig, FeedMessage, OrderbookCallback};
+use flate2::read::GzDecoder;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+/// Pace at which recorded frames are replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Keep the recorded gaps between frames
+    Original,
+    /// Divide the recorded gaps by this factor
+    Accelerated(f64),
+    /// No delays at all
+    AsFastAsPossible,
+}
+
+impl std::str::FromStr for ReplaySpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "original" | "realtime" => Ok(Self::Original),
+            "max" | "fast" => Ok(Self::AsFastAsPossible),
+            other => {
+                let factor: f64 = other
+                    .trim_end_matches('x')
+                    .parse()
+                    .map_err(|_| format!("Invalid replay speed: {}", s))?;
+                if !(factor.is_finite() && factor > 0.0) {
+                    return Err(format!("Replay speed must be positive: {}", s));
+                }
+                Ok(if factor == 1.0 { Self::Original } else { Self::Accelerated(factor) })
+            }
+        }
+    }
+}
+
+/// What to replay and how fast
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    /// A recording, or a directory whose recordings are replayed in name order
+    pub path: PathBuf,
+    pub speed: ReplaySpeed,
+}
+
+/// Replay recorded frames through `callback`, returning once every file was read.
+///
+/// Checksum mismatches drop the local book just like a live session; the
+/// recording itself contains the fresh snapshot the live feed received.
+pub async fn run_replay(
+    callback: Arc<dyn OrderbookCallback>,
+    config: &FeedConfig,
+    replay: &ReplayConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut session = new_session(config)?;
+    let files = recording_files(&replay.path)?;
+    if files.is_empty() {
+        return Err(format!("No recordings found at {}", replay.path.display()).into());
+    }
+    tracing::info!(
+        "Replaying {} recording(s) from {} at {:?}",
+        files.len(),
+        replay.path.display(),
+        replay.speed
+    );
+
+    // Decompression happens off the async runtime
+    let (tx, mut rx) = mpsc::channel(1024);
+    std::thread::Builder::new()
+        .name("kraken-replay".to_string())
+        .spawn(move || read_recordings(files, |frame| tx.blocking_send(frame).is_ok()))?;
+
+    let mut clock: Option<(Instant, chrono::DateTime<chrono::Utc>)> = None;
+    let mut frames = 0u64;
+    // Pairs seen in the recording, marked stale once it ends
+    let mut symbols = BTreeSet::new();
+
+    callback.on_connected(0);
+
+    while let Some(recorded) = rx.recv().await {
+        let RecordedFrame { received_at, frame } = recorded;
+
+        // Sleep until the frame's recorded offset from the first frame, scaled by speed
+        let factor = match replay.speed {
+            ReplaySpeed::Original => Some(1.0),
+            ReplaySpeed::Accelerated(factor) => Some(factor),
+            ReplaySpeed::AsFastAsPossible => None,
+        };
+        if let Some(factor) = factor {
+            let (started, first) = *clock.get_or_insert((Instant::now(), received_at));
+            let offset = (received_at - first).to_std().unwrap_or_default();
+            tokio::time::sleep_until(started + Duration::from_secs_f64(offset.as_secs_f64() / factor)).await;
+        }
+
+        for message in session.handle_frame(&frame, received_at) {
+            if let FeedMessage::Snapshot(snapshot) = &message {
+                symbols.insert(snapshot.symbol.clone());
+            }
+            if let Some(symbol) = dispatch(message, callback.as_ref()) {
+                session.resubscribe(&symbol);
+            }
+        }
+        frames += 1;
+    }
+
+    tracing::info!("Replay finished after {} frames", frames);
+    callback.on_disconnected(0, &symbols.into_iter().collect::<Vec<_>>());
+
+    Ok(())
+}
+
+/// Recordings at `path`: the file itself, or the directory's recordings sorted by name
+fn recording_files(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.to_string_lossy().ends_with(RECORDING_EXTENSION))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Every frame of the recording at `path`, or of the directory's recordings in name order
+pub fn load_recordings(path: &Path) -> std::io::Result<Vec<RecordedFrame>> {
+    let mut frames = Vec::new();
+    read_recordings(recording_files(path)?, |frame| {
+        frames.push(frame);
+        true
+    });
+    Ok(frames)
+}
+
+/// Decode recordings in order and pass their frames on until `on_frame` returns `false`
+fn read_recordings(files: Vec<PathBuf>, mut on_frame: impl FnMut(RecordedFrame) -> bool) {
+    for path in files {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                tracing::error!("Failed to open recording {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        for (index, line) in BufReader::new(GzDecoder::new(file)).lines().enumerate() {
+            // A recording cut short by a crash ends in a truncated gzip stream
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    tracing::warn!("Stopped reading {} at line {}: {}", path.display(), index + 1, e);
+                    break;
+                }
+            };
+            match serde_json::from_str::<RecordedFrame>(&line) {
+                Ok(frame) => {
+                    if !on_frame(frame) {
+                        return;
+                    }
+                }
+                Err(e) => tracing::warn!("Skipping line {} of {}: {}", index + 1, path.display(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::Venue;
+    use crate::feed::{BookError, FrameRecorder, RecorderConfig, SubscriptionStatus};
+    use crate::market_data::{Candle, Spread, Ticker};
+    use crate::storage::{OrderbookSnapshot, Trade};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Collector {
+        books: Mutex<Vec<OrderbookSnapshot>>,
+    }
+
+    impl OrderbookCallback for Collector {
+        fn on_orderbook(&self, snapshot: OrderbookSnapshot) {
+            self.books.lock().unwrap().push(snapshot);
+        }
+        fn on_connected(&self, _connection: usize) {}
+        fn on_disconnected(&self, _connection: usize, _symbols: &[String]) {}
+        fn on_error(&self, _error: String) {}
+        fn on_checksum_mismatch(&self, _symbol: &str, _expected: u32, _computed: u32) {}
+        fn on_invalid_book(&self, _symbol: &str, _reason: &BookError) {}
+        fn on_trade(&self, _trade: Trade) {}
+        fn on_ticker(&self, _ticker: Ticker) {}
+        fn on_spread(&self, _spread: Spread) {}
+        fn on_candle(&self, _candle: Candle) {}
+        fn on_subscription_status(&self, _symbol: &str, _status: SubscriptionStatus) {}
+    }
+
+    #[test]
+    fn parses_speeds() {
+        assert_eq!("original".parse(), Ok(ReplaySpeed::Original));
+        assert_eq!("1".parse(), Ok(ReplaySpeed::Original));
+        assert_eq!("10x".parse(), Ok(ReplaySpeed::Accelerated(10.0)));
+        assert_eq!("max".parse(), Ok(ReplaySpeed::AsFastAsPossible));
+        assert!("0".parse::<ReplaySpeed>().is_err());
+        assert!("soon".parse::<ReplaySpeed>().is_err());
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_books_deterministically() {
+        let dir = std::env::temp_dir().join(format!("kraken-replay-test-{}", std::process::id()));
+        let recorder = FrameRecorder::start(
+            RecorderConfig {
+                dir: dir.clone(),
+                max_file_bytes: u64::MAX,
+                max_file_age: Duration::from_secs(3600),
+            },
+            "kraken-v1",
+        )
+        .unwrap();
+        let received_at = chrono::Utc::now();
+        recorder.record(received_at, r#"{"event":"heartbeat"}"#);
+        recorder.record(
+            received_at,
+            r#"[336,{"as":[["5541.30000","2.50700000","1534614248.123678"]],"bs":[["5541.20000","1.52900000","1534614248.765567"]]},"book-10","XBT/USD"]"#,
+        );
+        drop(recorder);
+
+        let replay = ReplayConfig {
+            path: dir.clone(),
+            speed: ReplaySpeed::AsFastAsPossible,
+        };
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let collector = Arc::new(Collector::default());
+            run_replay(collector.clone(), &FeedConfig::default(), &replay).await.unwrap();
+            let books = collector.books.lock().unwrap();
+            runs.push(serde_json::to_string(&*books).unwrap());
+        }
+        let synthetic = FeedConfig {
+            venue: Venue::Synthetic,
+            ..FeedConfig::default()
+        };
+        let refused = run_replay(Arc::new(Collector::default()), &synthetic, &replay).await;
+        let loaded = load_recordings(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let frames: Vec<_> = loaded.iter().map(|recorded| recorded.frame.as_str()).collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], r#"{"event":"heartbeat"}"#);
+
+        assert!(refused.is_err(), "synthetic books have no feed to replay");
+
+        assert!(runs[0].contains("XBT/USD"), "the recorded snapshot is replayed: {}", runs[0]);
+        assert_eq!(runs[0], runs[1]);
+    }
+}
@@ -0,0 +1,536 @@
+//! Kraken WebSocket API v1 (legacy array protocol)
+
+pub mod messages;
+
+pub use messages::ParseError;
+
+use crate::exchange::{ExchangeAdapter, Venue};
+use crate::feed::book::{BookError, OrderBook, PendingSnapshots, Side};
+use crate::feed::{
+    frame_excerpt, BookSubscription, FeedConfig, FeedMessage, SubscriptionStatus, DEFAULT_BOOK_DEPTH,
+};
+use crate::market_data::{Candle, Spread, Ticker};
+use crate::storage::{PriceLevel, Trade, TradeOrderType, TradeSide};
+use chrono::{DateTime, Utc};
+use messages::{
+    BookLevel, BookPayload, ChannelData, Event, Message, OhlcEntry, SpreadEntry, SubscriptionState, TradeEntry,
+    WireOrderType, WireSide,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+const URL: &str = "wss://ws.kraken.com";
+
+/// Kraken subscription request (also used for unsubscribing)
+#[derive(Debug, Serialize)]
+struct SubscribeRequest {
+    event: String,
+    pair: Vec<String>,
+    subscription: SubscriptionDetails,
+}
+
+#[derive(Debug, Serialize)]
+struct SubscriptionDetails {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u32>,
+}
+
+impl SubscriptionDetails {
+    fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            depth: None,
+            interval: None,
+        }
+    }
+}
+
+/// Build a subscribe/unsubscribe frame for a channel on the given pairs
+fn channel_request(event: &str, pairs: Vec<String>, subscription: SubscriptionDetails) -> String {
+    let request = SubscribeRequest {
+        event: event.to_string(),
+        pair: pairs,
+        subscription,
+    };
+    serde_json::to_string(&request).expect("subscription request serializes")
+}
+
+/// Build a book subscribe/unsubscribe frame for the given pairs
+fn book_request(event: &str, pairs: Vec<String>, depth: usize) -> String {
+    let subscription = SubscriptionDetails {
+        depth: Some(depth as i32),
+        ..SubscriptionDetails::named("book")
+    };
+    channel_request(event, pairs, subscription)
+}
+
+/// Non-book channels enabled by the configuration
+fn channel_subscriptions(config: &FeedConfig) -> Vec<SubscriptionDetails> {
+    let mut channels = Vec::new();
+    if config.trades {
+        channels.push(SubscriptionDetails::named("trade"));
+    }
+    if config.ticker {
+        channels.push(SubscriptionDetails::named("ticker"));
+    }
+    if config.spread {
+        channels.push(SubscriptionDetails::named("spread"));
+    }
+    if let Some(interval) = config.ohlc_interval {
+        channels.push(SubscriptionDetails {
+            interval: Some(interval),
+            ..SubscriptionDetails::named("ohlc")
+        });
+    }
+    channels
+}
+
+/// v1 connection state: local books keyed by pair
+pub(crate) struct V1Session {
+    orderbooks: HashMap<String, OrderBook>,
+    /// Subscribed depth per pair
+    depths: HashMap<String, usize>,
+    pending: PendingSnapshots,
+    /// Request id of the last ping sent
+    ping_id: u64,
+}
+
+impl V1Session {
+    pub(crate) fn new() -> Self {
+        Self {
+            orderbooks: HashMap::new(),
+            depths: HashMap::new(),
+            pending: PendingSnapshots::default(),
+            ping_id: 0,
+        }
+    }
+}
+
+impl ExchangeAdapter for V1Session {
+    fn venue(&self) -> Venue {
+        Venue::Kraken
+    }
+
+    fn url(&self) -> &'static str {
+        URL
+    }
+
+    fn subscribe_frames(&mut self, config: &FeedConfig) -> Vec<String> {
+        if config.subscriptions.is_empty() {
+            return Vec::new();
+        }
+
+        // One request per depth, since depth applies to the whole request
+        let mut pairs_by_depth: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for sub in &config.subscriptions {
+            self.depths.insert(sub.pair.to_string(), sub.depth);
+            self.pending.expect(&sub.pair.to_string());
+            pairs_by_depth.entry(sub.depth).or_default().push(sub.pair.to_string());
+        }
+
+        let mut frames: Vec<String> = pairs_by_depth
+            .into_iter()
+            .map(|(depth, pairs)| book_request("subscribe", pairs, depth))
+            .collect();
+
+        let pairs: Vec<String> = config.subscriptions.iter().map(|s| s.pair.to_string()).collect();
+        frames.extend(
+            channel_subscriptions(config)
+                .into_iter()
+                .map(|subscription| channel_request("subscribe", pairs.clone(), subscription)),
+        );
+
+        frames
+    }
+
+    fn handle_frame(&mut self, text: &str, received_at: DateTime<Utc>) -> Vec<FeedMessage> {
+        match parse_kraken_frame(text, &mut self.orderbooks, received_at) {
+            Ok(message) => message.into_iter().filter(|m| self.pending.admit(m)).collect(),
+            Err(e) => {
+                tracing::warn!("Unrecognized Kraken v1 frame ({}): {}", e, frame_excerpt(text));
+                Vec::new()
+            }
+        }
+    }
+
+    fn subscribe_symbol(&mut self, sub: &BookSubscription, config: &FeedConfig) -> Vec<String> {
+        self.depths.insert(sub.pair.to_string(), sub.depth);
+        self.pending.expect(&sub.pair.to_string());
+        let mut frames = vec![book_request("subscribe", vec![sub.pair.to_string()], sub.depth)];
+        frames.extend(
+            channel_subscriptions(config)
+                .into_iter()
+                .map(|subscription| channel_request("subscribe", vec![sub.pair.to_string()], subscription)),
+        );
+        frames
+    }
+
+    fn unsubscribe_symbol(&mut self, symbol: &str, config: &FeedConfig) -> Vec<String> {
+        self.orderbooks.remove(symbol);
+        self.pending.forget(symbol);
+        let depth = self.depths.remove(symbol).unwrap_or(DEFAULT_BOOK_DEPTH);
+
+        let mut frames = vec![book_request("unsubscribe", vec![symbol.to_string()], depth)];
+        frames.extend(
+            channel_subscriptions(config)
+                .into_iter()
+                .map(|subscription| channel_request("unsubscribe", vec![symbol.to_string()], subscription)),
+        );
+        frames
+    }
+
+    fn resubscribe(&mut self, symbol: &str) -> Vec<String> {
+        self.orderbooks.remove(symbol);
+        self.pending.expect(symbol);
+        let depth = self.depths.get(symbol).copied().unwrap_or(DEFAULT_BOOK_DEPTH);
+
+        ["unsubscribe", "subscribe"]
+            .iter()
+            .map(|event| book_request(event, vec![symbol.to_string()], depth))
+            .collect()
+    }
+
+    fn ping_frame(&mut self) -> Option<String> {
+        self.ping_id += 1;
+        Some(serde_json::json!({ "event": "ping", "reqid": self.ping_id }).to_string())
+    }
+}
+
+/// Parse Kraken WebSocket message and apply it to the tracked books.
+///
+/// Control messages other than heartbeats yield `Ok(None)`.
+pub fn parse_kraken_message(
+    text: &str,
+    orderbooks: &mut HashMap<String, OrderBook>,
+) -> Result<Option<FeedMessage>, ParseError> {
+    parse_kraken_frame(text, orderbooks, Utc::now())
+}
+
+/// Like [`parse_kraken_message`], for a frame received at `received_at`
+/// (e.g. when replaying a recording)
+pub fn parse_kraken_frame(
+    text: &str,
+    orderbooks: &mut HashMap<String, OrderBook>,
+    received_at: DateTime<Utc>,
+) -> Result<Option<FeedMessage>, ParseError> {
+    let message = match Message::parse(text)? {
+        Message::Event(event) => return Ok(handle_event(event)),
+        Message::Channel(message) => message,
+    };
+    let pair = message.pair;
+
+    tracing::debug!("Received message for pair: {}, channel: {}", pair, message.channel_id);
+
+    let feed_message = match message.data {
+        ChannelData::Book { depth, payloads } => {
+            apply_book(&pair, depth, payloads, orderbooks, received_at)
+        }
+        ChannelData::Trades(entries) => FeedMessage::Trades(
+            entries
+                .into_iter()
+                .map(|TradeEntry(price, volume, timestamp, side, order_type, _)| Trade {
+                    symbol: pair.clone(),
+                    price,
+                    volume,
+                    side: match side {
+                        WireSide::Buy => TradeSide::Buy,
+                        WireSide::Sell => TradeSide::Sell,
+                    },
+                    order_type: match order_type {
+                        WireOrderType::Market => TradeOrderType::Market,
+                        WireOrderType::Limit => TradeOrderType::Limit,
+                    },
+                    timestamp,
+                    trade_id: None,
+                })
+                .collect(),
+        ),
+        ChannelData::Ticker(ticker) => FeedMessage::Ticker(Ticker {
+            symbol: pair,
+            bid: ticker.b.0,
+            bid_volume: ticker.b.2,
+            ask: ticker.a.0,
+            ask_volume: ticker.a.2,
+            last: ticker.c.0,
+            volume_24h: ticker.v.1,
+            vwap_24h: ticker.p.1,
+            low_24h: ticker.l.1,
+            high_24h: ticker.h.1,
+            open_24h: Some(ticker.o.1),
+            trades_24h: Some(ticker.t.1),
+            timestamp: received_at,
+        }),
+        ChannelData::Spread(SpreadEntry(bid, ask, timestamp, bid_volume, ask_volume)) => {
+            FeedMessage::Spread(Spread {
+                symbol: pair,
+                bid,
+                ask,
+                bid_volume,
+                ask_volume,
+                timestamp,
+            })
+        }
+        ChannelData::Ohlc { interval, candle } => {
+            let OhlcEntry(_, end, open, high, low, close, vwap, volume, trades) = candle;
+            FeedMessage::Candle(Candle {
+                symbol: pair,
+                interval,
+                start: end - chrono::Duration::minutes(interval as i64),
+                open,
+                high,
+                low,
+                close,
+                vwap,
+                volume,
+                trades,
+            })
+        }
+    };
+
+    Ok(Some(feed_message))
+}
+
+/// Log control messages; heartbeats and book subscription results are passed on
+fn handle_event(event: Event) -> Option<FeedMessage> {
+    match event {
+        Event::Heartbeat => return Some(FeedMessage::Heartbeat),
+        Event::SystemStatus { status, version, .. } => {
+            tracing::info!("Kraken system status: {} (API {})", status, version.unwrap_or_default());
+        }
+        Event::SubscriptionStatus(status) => {
+            let channel = status
+                .subscription
+                .as_ref()
+                .map(|s| s.name.as_str())
+                .or(status.channel_name.as_deref())
+                .unwrap_or_default();
+            let pair = status.pair.clone().unwrap_or_default();
+
+            match status.status {
+                SubscriptionState::Error => {
+                    let reason = status.error_message.unwrap_or_default();
+                    tracing::warn!("Kraken rejected {} subscription for {}: {}", channel, pair, reason);
+                    // A pair that is rejected on any channel is unusable
+                    if status.pair.is_some() {
+                        return Some(FeedMessage::SubscriptionStatus {
+                            symbol: pair,
+                            status: SubscriptionStatus::Rejected { reason },
+                        });
+                    }
+                }
+                SubscriptionState::Subscribed if channel.starts_with("book") && status.pair.is_some() => {
+                    tracing::info!("Kraken subscribed {} for {}", channel, pair);
+                    return Some(FeedMessage::SubscriptionStatus {
+                        symbol: pair,
+                        status: SubscriptionStatus::Subscribed,
+                    });
+                }
+                state => tracing::info!("Kraken subscription {} for {} is {:?}", channel, pair, state),
+            }
+        }
+        Event::Pong { .. } => tracing::trace!("Kraken pong"),
+        Event::Error { error_message, .. } => tracing::warn!("Kraken error: {}", error_message),
+    }
+    None
+}
+
+/// Apply book payloads to the local book for `pair`.
+///
+/// A snapshot starts a new book; updates are refused until one was received.
+fn apply_book(
+    pair: &str,
+    depth: Option<usize>,
+    payloads: Vec<BookPayload>,
+    orderbooks: &mut HashMap<String, OrderBook>,
+    received_at: DateTime<Utc>,
+) -> FeedMessage {
+    let is_snapshot = payloads
+        .iter()
+        .any(|payload| payload.ask_snapshot.is_some() || payload.bid_snapshot.is_some());
+    if is_snapshot {
+        // Sized from the channel name (e.g. "book-100")
+        orderbooks.insert(pair.to_string(), OrderBook::new(depth.unwrap_or(DEFAULT_BOOK_DEPTH)));
+    }
+    let Some(book) = orderbooks.get_mut(pair) else {
+        return FeedMessage::InvalidBook {
+            symbol: pair.to_string(),
+            reason: BookError::MissingSnapshot,
+        };
+    };
+    if !is_snapshot {
+        book.advance();
+    }
+    let mut expected_checksum = None;
+    let mut exchange_time: Option<DateTime<Utc>> = None;
+
+    for payload in payloads {
+        if let Some(levels) = payload.ask_snapshot {
+            let levels = to_price_levels(levels);
+            exchange_time = latest_timestamp(exchange_time, &levels);
+            tracing::info!("Parsed {} ask levels for {}", levels.len(), pair);
+            book.replace_side(Side::Ask, levels);
+        }
+        if let Some(levels) = payload.bid_snapshot {
+            let levels = to_price_levels(levels);
+            exchange_time = latest_timestamp(exchange_time, &levels);
+            tracing::info!("Parsed {} bid levels for {}", levels.len(), pair);
+            book.replace_side(Side::Bid, levels);
+        }
+        if let Some(levels) = payload.ask_updates {
+            let levels = to_price_levels(levels);
+            exchange_time = latest_timestamp(exchange_time, &levels);
+            for level in levels {
+                book.apply(Side::Ask, level);
+            }
+        }
+        if let Some(levels) = payload.bid_updates {
+            let levels = to_price_levels(levels);
+            exchange_time = latest_timestamp(exchange_time, &levels);
+            for level in levels {
+                book.apply(Side::Bid, level);
+            }
+        }
+        // Update messages carry a checksum of the resulting book
+        if payload.checksum.is_some() {
+            expected_checksum = payload.checksum;
+        }
+    }
+
+    if let Err(reason) = book.validate() {
+        return FeedMessage::InvalidBook {
+            symbol: pair.to_string(),
+            reason,
+        };
+    }
+    if let Some(expected) = expected_checksum {
+        let computed = book.checksum();
+        if expected != computed {
+            return FeedMessage::ChecksumMismatch {
+                symbol: pair.to_string(),
+                expected,
+                computed,
+            };
+        }
+    }
+
+    let (bid_count, ask_count) = book.len();
+    tracing::debug!("Returning snapshot with {} bids, {} asks", bid_count, ask_count);
+
+    FeedMessage::Snapshot(book.to_snapshot(pair, exchange_time, received_at, expected_checksum))
+}
+
+fn to_price_levels(levels: Vec<BookLevel>) -> Vec<PriceLevel> {
+    levels
+        .into_iter()
+        .map(|BookLevel(price, volume, timestamp, _)| PriceLevel {
+            price,
+            volume,
+            order_count: None,
+            timestamp: Some(timestamp),
+        })
+        .collect()
+}
+
+/// Latest exchange time among `current` and the given levels
+fn latest_timestamp(current: Option<DateTime<Utc>>, levels: &[PriceLevel]) -> Option<DateTime<Utc>> {
+    levels.iter().filter_map(|l| l.timestamp).chain(current).max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Book from Kraken's v1 checksum documentation, whose checksum is 974947235
+    const BOOK_SNAPSHOT: &str = r#"[336,{"as":[["0.05005","0.00000500","1582905487.684110"],["0.05010","0.00000500","1582905486.187983"],["0.05015","0.00000500","1582905484.480241"],["0.05020","0.00000500","1582905486.645658"],["0.05025","0.00000500","1582905486.859009"],["0.05030","0.00000500","1582905488.601486"],["0.05035","0.00000500","1582905488.357312"],["0.05040","0.00000500","1582905488.785484"],["0.05045","0.00000500","1582905485.302661"],["0.05050","0.00000500","1582905486.157467"]],"bs":[["0.05000","0.00000500","1582905487.439814"],["0.04995","0.00000500","1582905485.119396"],["0.04990","0.00000500","1582905486.432052"],["0.04980","0.00000500","1582905480.609351"],["0.04975","0.00000500","1582905476.793880"],["0.04970","0.00000500","1582905486.767461"],["0.04965","0.00000500","1582905481.767528"],["0.04960","0.00000500","1582905487.378907"],["0.04955","0.00000500","1582905483.626664"],["0.04950","0.00000500","1582905488.509872"]]},"book-10","XBT/USD"]"#;
+    /// Resends the best ask unchanged, so the book keeps its checksum
+    const BOOK_UPDATE: &str = r#"[336,{"a":[["0.05005","0.00000500","1582905489.000000"]],"c":"974947235"},"book-10","XBT/USD"]"#;
+    const BOOK_UPDATE_BAD_CHECKSUM: &str =
+        r#"[336,{"a":[["0.05005","0.00000500","1582905489.000000"]],"c":"1"},"book-10","XBT/USD"]"#;
+
+    fn subscribed_session() -> V1Session {
+        let mut session = V1Session::new();
+        session.subscribe_frames(&FeedConfig {
+            subscriptions: vec![BookSubscription::new("XBT/USD", 10).unwrap()],
+            ..Default::default()
+        });
+        session
+    }
+
+    /// Parse a text frame received just now
+    fn handle_text(session: &mut impl ExchangeAdapter, text: &str) -> Vec<FeedMessage> {
+        session.handle_frame(text, Utc::now())
+    }
+
+    fn expect_snapshot(mut messages: Vec<FeedMessage>) -> crate::storage::OrderbookSnapshot {
+        assert_eq!(messages.len(), 1);
+        match messages.remove(0) {
+            FeedMessage::Snapshot(snapshot) => snapshot,
+            other => panic!("expected a book snapshot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn applies_snapshot_and_checksummed_update() {
+        let mut session = subscribed_session();
+        let snapshot = expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT));
+        assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (10, 10));
+        assert_eq!(snapshot.sequence, Some(0));
+
+        let update = expect_snapshot(handle_text(&mut session, BOOK_UPDATE));
+        assert_eq!(update.sequence, Some(1));
+        assert_eq!(update.checksum, Some(974947235));
+        assert_eq!(update.timestamp, DateTime::from_timestamp(1582905489, 0).unwrap());
+    }
+
+    #[test]
+    fn keeps_the_subscribed_depth() {
+        let mut session = subscribed_session();
+        expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT));
+
+        let better_ask = r#"[336,{"a":[["0.05001","0.00000500","1582905489.000000"]]},"book-10","XBT/USD"]"#;
+        let update = expect_snapshot(handle_text(&mut session, better_ask));
+        assert_eq!(update.asks.len(), 10);
+        assert_eq!(update.asks[0].price.to_string(), "0.05001");
+        assert_eq!(update.asks[9].price.to_string(), "0.05045", "the worst ask falls out of depth");
+    }
+
+    #[test]
+    fn reports_checksum_mismatch() {
+        let mut session = subscribed_session();
+        expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT));
+
+        assert!(matches!(
+            handle_text(&mut session, BOOK_UPDATE_BAD_CHECKSUM).as_slice(),
+            [FeedMessage::ChecksumMismatch { symbol, expected: 1, computed: 974947235 }] if symbol == "XBT/USD"
+        ));
+    }
+
+    #[test]
+    fn refuses_updates_before_snapshot() {
+        // Leftovers of a previous subscription are dropped while the snapshot is pending
+        let mut session = subscribed_session();
+        assert!(handle_text(&mut session, BOOK_UPDATE).is_empty());
+
+        let mut session = V1Session::new();
+        assert!(matches!(
+            handle_text(&mut session, BOOK_UPDATE).as_slice(),
+            [FeedMessage::InvalidBook { symbol, reason: BookError::MissingSnapshot }] if symbol == "XBT/USD"
+        ));
+    }
+
+    #[test]
+    fn resubscribes_at_the_subscribed_depth() {
+        let mut session = subscribed_session();
+        expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT));
+
+        let frames = session.resubscribe("XBT/USD");
+        assert_eq!(frames.len(), 2);
+        let unsubscribe: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(unsubscribe["event"], "unsubscribe");
+        assert_eq!(unsubscribe["subscription"]["depth"], 10);
+        assert!(handle_text(&mut session, BOOK_UPDATE).is_empty(), "the dropped book waits for a new snapshot");
+    }
+}
@@ -0,0 +1,407 @@
+//! Typed Kraken WebSocket v1 messages
+//!
+//! Control messages are JSON objects tagged by `event`. Channel data are
+//! arrays `[channelID, payload, channelName, pair]`; book updates touching
+//! both sides may instead carry the asks and bids as two separate payload
+//! objects: `[channelID, {"a": ...}, {"b": ..., "c": ...}, channelName, pair]`.
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::de::{Error as _, IgnoredAny};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+/// Any frame received from the v1 API
+#[derive(Debug)]
+pub enum Message {
+    Event(Event),
+    Channel(ChannelMessage),
+}
+
+/// Control message, tagged by its `event` field
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Event {
+    SystemStatus {
+        #[serde(rename = "connectionID")]
+        connection_id: Option<u64>,
+        status: String,
+        version: Option<String>,
+    },
+    SubscriptionStatus(SubscriptionStatus),
+    Heartbeat,
+    Pong {
+        reqid: Option<u64>,
+    },
+    Error {
+        #[serde(rename = "errorMessage")]
+        error_message: String,
+        reqid: Option<u64>,
+    },
+}
+
+/// Result of a subscribe or unsubscribe request for one pair
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionStatus {
+    #[serde(rename = "channelID")]
+    pub channel_id: Option<u64>,
+    #[serde(rename = "channelName")]
+    pub channel_name: Option<String>,
+    pub pair: Option<String>,
+    pub status: SubscriptionState,
+    pub subscription: Option<SubscriptionInfo>,
+    #[serde(rename = "errorMessage")]
+    pub error_message: Option<String>,
+    pub reqid: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionState {
+    Subscribed,
+    Unsubscribed,
+    Error,
+}
+
+/// Subscription echoed back in a `subscriptionStatus`
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionInfo {
+    pub name: String,
+    pub depth: Option<usize>,
+    pub interval: Option<u32>,
+}
+
+/// Data pushed on a subscribed channel
+#[derive(Debug)]
+pub struct ChannelMessage {
+    pub channel_id: u64,
+    pub pair: String,
+    pub data: ChannelData,
+}
+
+#[derive(Debug)]
+pub enum ChannelData {
+    /// One payload, or two when asks and bids are sent separately
+    Book {
+        /// Depth from the channel name (`book-100`)
+        depth: Option<usize>,
+        payloads: Vec<BookPayload>,
+    },
+    Trades(Vec<TradeEntry>),
+    Ticker(TickerPayload),
+    Spread(SpreadEntry),
+    Ohlc {
+        /// Interval in minutes from the channel name (`ohlc-5`)
+        interval: u32,
+        candle: OhlcEntry,
+    },
+}
+
+/// Book snapshot (`as`/`bs`) and/or update (`a`/`b`/`c`) object
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BookPayload {
+    #[serde(rename = "as")]
+    pub ask_snapshot: Option<Vec<BookLevel>>,
+    #[serde(rename = "bs")]
+    pub bid_snapshot: Option<Vec<BookLevel>>,
+    #[serde(rename = "a")]
+    pub ask_updates: Option<Vec<BookLevel>>,
+    #[serde(rename = "b")]
+    pub bid_updates: Option<Vec<BookLevel>>,
+    /// CRC32 of the top 10 levels after applying the update
+    #[serde(rename = "c", default, deserialize_with = "checksum")]
+    pub checksum: Option<u32>,
+}
+
+/// `[price, volume, timestamp]`, with a trailing `"r"` on republished updates
+#[derive(Debug, Deserialize)]
+pub struct BookLevel(
+    pub Decimal,
+    pub Decimal,
+    #[serde(deserialize_with = "kraken_time")] pub DateTime<Utc>,
+    #[serde(default)] pub Option<String>,
+);
+
+/// `[price, volume, time, side, orderType, misc]`
+#[derive(Debug, Deserialize)]
+pub struct TradeEntry(
+    pub Decimal,
+    pub Decimal,
+    #[serde(deserialize_with = "kraken_time")] pub DateTime<Utc>,
+    pub WireSide,
+    pub WireOrderType,
+    pub String,
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum WireSide {
+    #[serde(rename = "b")]
+    Buy,
+    #[serde(rename = "s")]
+    Sell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum WireOrderType {
+    #[serde(rename = "m")]
+    Market,
+    #[serde(rename = "l")]
+    Limit,
+}
+
+/// Ticker object; two-element fields are `[today, last 24 hours]`
+#[derive(Debug, Deserialize)]
+pub struct TickerPayload {
+    /// Best ask `[price, wholeLotVolume, lotVolume]`
+    pub a: (Decimal, IgnoredAny, Decimal),
+    /// Best bid `[price, wholeLotVolume, lotVolume]`
+    pub b: (Decimal, IgnoredAny, Decimal),
+    /// Last trade `[price, lotVolume]`
+    pub c: (Decimal, Decimal),
+    /// Volume
+    pub v: (Decimal, Decimal),
+    /// Volume weighted average price
+    pub p: (Decimal, Decimal),
+    /// Number of trades
+    pub t: (u64, u64),
+    pub l: (Decimal, Decimal),
+    pub h: (Decimal, Decimal),
+    pub o: (Decimal, Decimal),
+}
+
+/// `[bid, ask, timestamp, bidVolume, askVolume]`
+#[derive(Debug, Deserialize)]
+pub struct SpreadEntry(
+    pub Decimal,
+    pub Decimal,
+    #[serde(deserialize_with = "kraken_time")] pub DateTime<Utc>,
+    pub Decimal,
+    pub Decimal,
+);
+
+/// `[time, etime, open, high, low, close, vwap, volume, count]`
+#[derive(Debug, Deserialize)]
+pub struct OhlcEntry(
+    #[serde(deserialize_with = "kraken_time")] pub DateTime<Utc>,
+    /// End of the interval the candle belongs to
+    #[serde(deserialize_with = "kraken_time")]
+    pub DateTime<Utc>,
+    pub Decimal,
+    pub Decimal,
+    pub Decimal,
+    pub Decimal,
+    pub Decimal,
+    pub Decimal,
+    pub u64,
+);
+
+/// Frame that could not be decoded into a [`Message`]
+#[derive(Debug)]
+pub enum ParseError {
+    /// Not a JSON object or array
+    NotAFrame,
+    /// Object that is not a known event, or an event with missing/invalid fields
+    Event(serde_json::Error),
+    /// Array that is not `[channelID, payload(s), channelName, pair]`
+    Frame(serde_json::Error),
+    /// Channel name this client does not handle
+    UnknownChannel { channel: String, pair: String },
+    /// Payload that does not match its channel's schema
+    Payload {
+        channel: String,
+        pair: String,
+        source: serde_json::Error,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::NotAFrame => write!(f, "frame is not a JSON object or array"),
+            ParseError::Event(e) => write!(f, "invalid event message: {}", e),
+            ParseError::Frame(e) => write!(f, "invalid channel frame: {}", e),
+            ParseError::UnknownChannel { channel, pair } => {
+                write!(f, "unknown channel {} for {}", channel, pair)
+            }
+            ParseError::Payload { channel, pair, source } => {
+                write!(f, "invalid {} payload for {}: {}", channel, pair, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Event(e) | ParseError::Frame(e) | ParseError::Payload { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Channel frame before its payload is decoded; the payload type is only
+/// known from the channel name, which comes after it
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawChannelFrame {
+    Single(u64, serde_json::Value, String, String),
+    Split(u64, serde_json::Value, serde_json::Value, String, String),
+}
+
+impl Message {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        match text.trim_start().as_bytes().first() {
+            Some(b'{') => serde_json::from_str(text).map(Message::Event).map_err(ParseError::Event),
+            Some(b'[') => {
+                let frame = serde_json::from_str(text).map_err(ParseError::Frame)?;
+                ChannelMessage::decode(frame).map(Message::Channel)
+            }
+            _ => Err(ParseError::NotAFrame),
+        }
+    }
+}
+
+impl ChannelMessage {
+    fn decode(frame: RawChannelFrame) -> Result<Self, ParseError> {
+        let (channel_id, payloads, channel, pair) = match frame {
+            RawChannelFrame::Single(id, payload, channel, pair) => (id, vec![payload], channel, pair),
+            RawChannelFrame::Split(id, asks, bids, channel, pair) => (id, vec![asks, bids], channel, pair),
+        };
+
+        let payload_error = |source| ParseError::Payload {
+            channel: channel.clone(),
+            pair: pair.clone(),
+            source,
+        };
+        let single = |payloads: Vec<serde_json::Value>| match <[serde_json::Value; 1]>::try_from(payloads) {
+            Ok([payload]) => Ok(payload),
+            Err(_) => Err(payload_error(serde_json::Error::custom("expected a single payload"))),
+        };
+
+        let data = match channel.as_str() {
+            "trade" => ChannelData::Trades(serde_json::from_value(single(payloads)?).map_err(payload_error)?),
+            "ticker" => ChannelData::Ticker(serde_json::from_value(single(payloads)?).map_err(payload_error)?),
+            "spread" => ChannelData::Spread(serde_json::from_value(single(payloads)?).map_err(payload_error)?),
+            name if name.starts_with("ohlc-") => ChannelData::Ohlc {
+                interval: name["ohlc-".len()..]
+                    .parse()
+                    .map_err(|_| payload_error(serde_json::Error::custom("invalid interval in channel name")))?,
+                candle: serde_json::from_value(single(payloads)?).map_err(payload_error)?,
+            },
+            name if name == "book" || name.starts_with("book-") => ChannelData::Book {
+                depth: name.strip_prefix("book-").and_then(|d| d.parse().ok()),
+                payloads: payloads
+                    .into_iter()
+                    .map(serde_json::from_value)
+                    .collect::<Result<_, _>>()
+                    .map_err(payload_error)?,
+            },
+            _ => return Err(ParseError::UnknownChannel { channel, pair }),
+        };
+
+        Ok(Self { channel_id, pair, data })
+    }
+}
+
+/// Parse a Kraken timestamp given as fractional Unix seconds, e.g. "1534614248.456738"
+pub fn parse_kraken_time(value: &str) -> Option<DateTime<Utc>> {
+    let (secs, frac) = value.split_once('.').unwrap_or((value, ""));
+    let secs: i64 = secs.parse().ok()?;
+    // Checked before slicing, which would panic inside a multibyte character
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos: u32 = if frac.is_empty() {
+        0
+    } else {
+        format!("{:0<9}", &frac[..frac.len().min(9)]).parse().ok()?
+    };
+    DateTime::from_timestamp(secs, nanos)
+}
+
+fn kraken_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_kraken_time(&value).ok_or_else(|| D::Error::custom(format!("invalid timestamp {:?}", value)))
+}
+
+fn checksum<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| D::Error::custom(format!("invalid checksum {:?}", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_book_snapshot() {
+        let text = r#"[0,{"as":[["5541.30000","2.50700000","1534614248.123678"]],"bs":[["5541.20000","1.52900000","1534614248.765567"]]},"book-100","XBT/USD"]"#;
+        let Message::Channel(message) = Message::parse(text).unwrap() else { panic!("expected channel message") };
+
+        assert_eq!(message.pair, "XBT/USD");
+        let ChannelData::Book { depth, payloads } = message.data else { panic!("expected book") };
+        assert_eq!(depth, Some(100));
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0].ask_snapshot.as_ref().unwrap()[0].0.to_string(), "5541.30000");
+        assert!(payloads[0].ask_updates.is_none());
+    }
+
+    #[test]
+    fn parses_split_book_update() {
+        let text = r#"[1234,{"a":[["5541.30000","2.50700000","1534614248.456738"],["5542.50000","0.40100000","1534614248.456738"]]},{"b":[["5541.30000","0.00000000","1534614335.345903","r"]],"c":"974942666"},"book-10","XBT/USD"]"#;
+        let Message::Channel(message) = Message::parse(text).unwrap() else { panic!("expected channel message") };
+
+        let ChannelData::Book { payloads, .. } = message.data else { panic!("expected book") };
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0].ask_updates.as_ref().unwrap().len(), 2);
+        let bids = payloads[1].bid_updates.as_ref().unwrap();
+        assert_eq!(bids[0].3.as_deref(), Some("r"));
+        assert_eq!(payloads[1].checksum, Some(974942666));
+    }
+
+    #[test]
+    fn parses_events() {
+        assert!(matches!(Message::parse(r#"{"event":"heartbeat"}"#), Ok(Message::Event(Event::Heartbeat))));
+
+        let text = r#"{"errorMessage":"Currency pair not supported XBT/USDX","event":"subscriptionStatus","pair":"XBT/USDX","status":"error","subscription":{"depth":10,"name":"book"}}"#;
+        let Ok(Message::Event(Event::SubscriptionStatus(status))) = Message::parse(text) else {
+            panic!("expected subscription status")
+        };
+        assert_eq!(status.status, SubscriptionState::Error);
+        assert_eq!(status.pair.as_deref(), Some("XBT/USDX"));
+        assert_eq!(status.subscription.unwrap().depth, Some(10));
+    }
+
+    #[test]
+    fn reports_malformed_frames() {
+        assert!(matches!(Message::parse(r#"{"event":"bogus"}"#), Err(ParseError::Event(_))));
+        assert!(matches!(Message::parse(r#"[1,"book-10"]"#), Err(ParseError::Frame(_))));
+        assert!(matches!(
+            Message::parse(r#"[1,{},"unknown","XBT/USD"]"#),
+            Err(ParseError::UnknownChannel { .. })
+        ));
+        assert!(matches!(
+            Message::parse(r#"[1,{"a":[["not-a-price","1.0","1534614248.456738"]]},"book-10","XBT/USD"]"#),
+            Err(ParseError::Payload { .. })
+        ));
+        assert!(matches!(Message::parse("not json"), Err(ParseError::NotAFrame)));
+    }
+
+    #[test]
+    fn rejects_timestamps_with_non_digit_fractions() {
+        assert_eq!(
+            parse_kraken_time("1534614248.123456789123"),
+            DateTime::from_timestamp(1534614248, 123456789)
+        );
+        assert_eq!(parse_kraken_time("1534614248.12345678é"), None);
+        assert_eq!(parse_kraken_time("1534614248.-5"), None);
+        assert!(matches!(
+            Message::parse(r#"[1,{"a":[["5541.30000","1.0","1534614248.12345678é"]]},"book-10","XBT/USD"]"#),
+            Err(ParseError::Payload { .. })
+        ));
+    }
+}
@@ -0,0 +1,870 @@
+//! Kraken WebSocket API v2 (typed JSON protocol)
+//!
+//! v2 names pairs with ISO asset codes (`BTC/USD`) and sends prices as JSON
+//! numbers. Symbols are translated to and from v1 naming at the edge so the
+//! rest of the backend keeps using `XBT/USD`. Book checksums are computed on
+//! values formatted to the pair's precision, which is learned from the
+//! `instrument` channel.
+
+use crate::exchange::{ExchangeAdapter, Venue};
+use crate::feed::book::{BookError, OrderBook, PendingSnapshots, Side};
+use crate::feed::{
+    frame_excerpt, BookSubscription, FeedConfig, FeedMessage, SubscriptionStatus, DEFAULT_BOOK_DEPTH,
+};
+use crate::market_data::{Candle, Spread, Ticker};
+use crate::storage::{PriceLevel, Trade, TradeOrderType, TradeSide};
+use crate::symbols::Symbol;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const URL: &str = "wss://ws.kraken.com/v2";
+
+/// Convert a v1 pair name (`XBT/USD`) to v2 naming (`BTC/USD`)
+fn to_v2_symbol(pair: &str) -> String {
+    Symbol::parse(pair).map(|symbol| symbol.v2_name()).unwrap_or_else(|_| pair.to_string())
+}
+
+/// Convert a v2 symbol (`BTC/USD`) back to v1 naming (`XBT/USD`)
+fn from_v2_symbol(symbol: &str) -> String {
+    Symbol::from_v2(symbol).map(|symbol| symbol.v1_name()).unwrap_or_else(|_| symbol.to_string())
+}
+
+/// Method call sent to Kraken (`subscribe` / `unsubscribe`)
+#[derive(Debug, Serialize)]
+struct MethodRequest {
+    method: &'static str,
+    params: SubscriptionParams,
+}
+
+#[derive(Debug, Serialize)]
+struct SubscriptionParams {
+    channel: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u32>,
+}
+
+impl SubscriptionParams {
+    fn channel(channel: &'static str) -> Self {
+        Self {
+            channel,
+            symbol: None,
+            depth: None,
+            snapshot: None,
+            interval: None,
+        }
+    }
+}
+
+/// Serialize a method call into a frame
+fn method_request(method: &'static str, params: SubscriptionParams) -> String {
+    serde_json::to_string(&MethodRequest { method, params }).expect("subscription request serializes")
+}
+
+/// Build a book subscribe/unsubscribe frame for the given v2 symbols
+fn book_request(method: &'static str, symbols: Vec<String>, depth: usize) -> String {
+    let params = SubscriptionParams {
+        symbol: Some(symbols),
+        depth: Some(depth),
+        snapshot: (method == "subscribe").then_some(true),
+        ..SubscriptionParams::channel("book")
+    };
+    method_request(method, params)
+}
+
+/// Any frame received from the v2 API
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Frame {
+    Channel(ChannelFrame),
+    Method(MethodResponse),
+}
+
+/// Channel data pushed by Kraken
+#[derive(Debug, Deserialize)]
+#[serde(tag = "channel", rename_all = "lowercase")]
+enum ChannelFrame {
+    Book {
+        #[serde(rename = "type")]
+        kind: UpdateKind,
+        data: Vec<BookData>,
+    },
+    Instrument {
+        data: InstrumentData,
+    },
+    Trade {
+        data: Vec<TradeData>,
+    },
+    Ticker {
+        data: Vec<TickerData>,
+    },
+    Ohlc {
+        data: Vec<OhlcData>,
+    },
+    Status {
+        data: Vec<serde_json::Value>,
+    },
+    Heartbeat,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum UpdateKind {
+    Snapshot,
+    Update,
+}
+
+#[derive(Debug, Deserialize)]
+struct BookData {
+    symbol: String,
+    #[serde(default)]
+    bids: Vec<BookLevel>,
+    #[serde(default)]
+    asks: Vec<BookLevel>,
+    checksum: Option<u32>,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BookLevel {
+    #[serde(deserialize_with = "decimal_from_number")]
+    price: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    qty: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeData {
+    symbol: String,
+    side: TradeSide,
+    #[serde(deserialize_with = "decimal_from_number")]
+    price: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    qty: Decimal,
+    ord_type: TradeOrderType,
+    trade_id: u64,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TickerData {
+    symbol: String,
+    #[serde(deserialize_with = "decimal_from_number")]
+    bid: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    bid_qty: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    ask: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    ask_qty: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    last: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    volume: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    vwap: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    low: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    high: Decimal,
+    /// Price change over the last 24 hours
+    #[serde(deserialize_with = "decimal_from_number")]
+    change: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct OhlcData {
+    symbol: String,
+    #[serde(deserialize_with = "decimal_from_number")]
+    open: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    high: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    low: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    close: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    vwap: Decimal,
+    #[serde(deserialize_with = "decimal_from_number")]
+    volume: Decimal,
+    trades: u64,
+    interval_begin: DateTime<Utc>,
+    interval: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstrumentData {
+    #[serde(default)]
+    pairs: Vec<InstrumentPair>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstrumentPair {
+    symbol: String,
+    price_precision: u32,
+    qty_precision: u32,
+}
+
+/// Acknowledgement of a method call
+#[derive(Debug, Deserialize)]
+struct MethodResponse {
+    method: String,
+    #[serde(default)]
+    success: bool,
+    error: Option<String>,
+    result: Option<MethodResult>,
+    /// Symbol a failed subscription refers to
+    symbol: Option<String>,
+}
+
+/// Subscription echoed back on success
+#[derive(Debug, Deserialize)]
+struct MethodResult {
+    channel: Option<String>,
+    symbol: Option<String>,
+}
+
+/// Read a JSON number into a `Decimal` from its exact text, without a lossy round trip through `f64`
+fn decimal_from_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    rust_decimal::serde::arbitrary_precision::deserialize(deserializer)
+}
+
+/// Decimal places Kraken uses for a pair's prices and quantities
+#[derive(Debug, Clone, Copy)]
+struct Precision {
+    price: u32,
+    qty: u32,
+}
+
+/// v2 connection state: local books keyed by v1 pair name
+pub(crate) struct V2Session {
+    orderbooks: HashMap<String, OrderBook>,
+    /// Subscribed depth per v1 pair name
+    depths: HashMap<String, usize>,
+    /// Pair precision keyed by v2 symbol
+    precisions: HashMap<String, Precision>,
+    /// Pairs already warned about for books received before their precision
+    unverified: HashSet<String>,
+    pending: PendingSnapshots,
+    emit_ticker: bool,
+    emit_spread: bool,
+    /// Request id of the last ping sent
+    ping_id: u64,
+}
+
+impl V2Session {
+    pub(crate) fn new() -> Self {
+        Self {
+            orderbooks: HashMap::new(),
+            depths: HashMap::new(),
+            precisions: HashMap::new(),
+            unverified: HashSet::new(),
+            pending: PendingSnapshots::default(),
+            emit_ticker: false,
+            emit_spread: false,
+            ping_id: 0,
+        }
+    }
+
+    /// Apply a book message; a snapshot starts a new book and updates are refused until one was received
+    fn apply_book(&mut self, kind: UpdateKind, data: BookData, received_at: DateTime<Utc>) -> FeedMessage {
+        let pair = from_v2_symbol(&data.symbol);
+        let precision = self.precisions.get(&data.symbol).copied();
+        if precision.is_none() && data.checksum.is_some() && self.unverified.insert(pair.clone()) {
+            tracing::warn!(
+                "No instrument precision for {} yet, its book checksums are not verified until it arrives",
+                pair
+            );
+        }
+        if let UpdateKind::Snapshot = kind {
+            let depth = self.depths.get(&pair).copied().unwrap_or(DEFAULT_BOOK_DEPTH);
+            self.orderbooks.insert(pair.clone(), OrderBook::new(depth));
+        }
+        let Some(book) = self.orderbooks.get_mut(&pair) else {
+            return FeedMessage::InvalidBook {
+                symbol: pair,
+                reason: BookError::MissingSnapshot,
+            };
+        };
+
+        // Snapshot levels carry no update time of their own
+        let level_time = match kind {
+            UpdateKind::Snapshot => None,
+            UpdateKind::Update => data.timestamp,
+        };
+        let to_level = |level: BookLevel| PriceLevel {
+            price: match precision {
+                Some(p) => with_scale(level.price, p.price),
+                None => level.price,
+            },
+            volume: match precision {
+                Some(p) => with_scale(level.qty, p.qty),
+                None => level.qty,
+            },
+            order_count: None,
+            timestamp: level_time,
+        };
+
+        match kind {
+            UpdateKind::Snapshot => {
+                tracing::info!(
+                    "Parsed {} bid and {} ask levels for {}",
+                    data.bids.len(),
+                    data.asks.len(),
+                    pair
+                );
+                book.replace_side(Side::Bid, data.bids.into_iter().map(to_level).collect());
+                book.replace_side(Side::Ask, data.asks.into_iter().map(to_level).collect());
+            }
+            UpdateKind::Update => {
+                book.advance();
+                for level in data.bids {
+                    book.apply(Side::Bid, to_level(level));
+                }
+                for level in data.asks {
+                    book.apply(Side::Ask, to_level(level));
+                }
+            }
+        }
+
+        if let Err(reason) = book.validate() {
+            return FeedMessage::InvalidBook { symbol: pair, reason };
+        }
+
+        // The checksum is only comparable once the pair precision is known
+        let expected_checksum = data.checksum.filter(|_| precision.is_some());
+        if let Some(expected) = expected_checksum {
+            let computed = book.checksum();
+            if expected != computed {
+                return FeedMessage::ChecksumMismatch {
+                    symbol: pair,
+                    expected,
+                    computed,
+                };
+            }
+        }
+
+        FeedMessage::Snapshot(book.to_snapshot(&pair, data.timestamp, received_at, expected_checksum))
+    }
+}
+
+impl V2Session {
+    /// Subscribe/unsubscribe frames for the non-book channels enabled by the configuration
+    fn channel_requests(&mut self, method: &'static str, symbols: Vec<String>, config: &FeedConfig) -> Vec<String> {
+        let mut frames = Vec::new();
+        if symbols.is_empty() {
+            return frames;
+        }
+
+        if config.trades {
+            // Skip the trade snapshot so reconnects do not store trades twice
+            let trade = SubscriptionParams {
+                symbol: Some(symbols.clone()),
+                snapshot: (method == "subscribe").then_some(false),
+                ..SubscriptionParams::channel("trade")
+            };
+            frames.push(method_request(method, trade));
+        }
+
+        // v2 has no spread channel; best bid/offer comes from the ticker
+        self.emit_ticker = config.ticker;
+        self.emit_spread = config.spread;
+        if config.ticker || config.spread {
+            let ticker = SubscriptionParams {
+                symbol: Some(symbols.clone()),
+                ..SubscriptionParams::channel("ticker")
+            };
+            frames.push(method_request(method, ticker));
+        }
+
+        if let Some(interval) = config.ohlc_interval {
+            let ohlc = SubscriptionParams {
+                symbol: Some(symbols),
+                interval: Some(interval),
+                ..SubscriptionParams::channel("ohlc")
+            };
+            frames.push(method_request(method, ohlc));
+        }
+
+        frames
+    }
+}
+
+/// Format a decimal with exactly `scale` decimal places
+fn with_scale(mut value: Decimal, scale: u32) -> Decimal {
+    value.rescale(scale);
+    value
+}
+
+impl ExchangeAdapter for V2Session {
+    fn venue(&self) -> Venue {
+        Venue::Kraken
+    }
+
+    fn url(&self) -> &'static str {
+        URL
+    }
+
+    fn subscribe_frames(&mut self, config: &FeedConfig) -> Vec<String> {
+        // Pair precision is needed before book checksums can be verified
+        let instrument = SubscriptionParams {
+            snapshot: Some(true),
+            ..SubscriptionParams::channel("instrument")
+        };
+        let mut frames = vec![method_request("subscribe", instrument)];
+
+        // One request per depth, since depth applies to the whole request
+        let mut symbols_by_depth: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for sub in &config.subscriptions {
+            self.depths.insert(sub.pair.to_string(), sub.depth);
+            self.pending.expect(&sub.pair.to_string());
+            symbols_by_depth
+                .entry(sub.depth)
+                .or_default()
+                .push(sub.pair.v2_name());
+        }
+
+        frames.extend(
+            symbols_by_depth
+                .into_iter()
+                .map(|(depth, symbols)| book_request("subscribe", symbols, depth)),
+        );
+
+        let symbols: Vec<String> = config.subscriptions.iter().map(|s| s.pair.v2_name()).collect();
+        frames.extend(self.channel_requests("subscribe", symbols, config));
+
+        frames
+    }
+
+    fn handle_frame(&mut self, text: &str, received_at: DateTime<Utc>) -> Vec<FeedMessage> {
+        let frame: Frame = match serde_json::from_str(text) {
+            Ok(frame) => frame,
+            Err(e) => {
+                tracing::warn!("Unrecognized Kraken v2 frame ({}): {}", e, frame_excerpt(text));
+                return Vec::new();
+            }
+        };
+
+        match frame {
+            Frame::Channel(ChannelFrame::Book { kind, data }) => {
+                let messages: Vec<FeedMessage> = data
+                    .into_iter()
+                    .map(|book| self.apply_book(kind, book, received_at))
+                    .collect();
+                messages.into_iter().filter(|message| self.pending.admit(message)).collect()
+            }
+            Frame::Channel(ChannelFrame::Trade { data }) => {
+                let trades = data
+                    .into_iter()
+                    .map(|trade| Trade {
+                        symbol: from_v2_symbol(&trade.symbol),
+                        price: trade.price,
+                        volume: trade.qty,
+                        side: trade.side,
+                        order_type: trade.ord_type,
+                        timestamp: trade.timestamp,
+                        trade_id: Some(trade.trade_id),
+                    })
+                    .collect();
+                vec![FeedMessage::Trades(trades)]
+            }
+            Frame::Channel(ChannelFrame::Ticker { data }) => {
+                let mut messages = Vec::new();
+                for ticker in data {
+                    let symbol = from_v2_symbol(&ticker.symbol);
+                    if self.emit_spread {
+                        messages.push(FeedMessage::Spread(Spread {
+                            symbol: symbol.clone(),
+                            bid: ticker.bid,
+                            ask: ticker.ask,
+                            bid_volume: ticker.bid_qty,
+                            ask_volume: ticker.ask_qty,
+                            timestamp: received_at,
+                        }));
+                    }
+                    if self.emit_ticker {
+                        messages.push(FeedMessage::Ticker(Ticker {
+                            symbol,
+                            bid: ticker.bid,
+                            bid_volume: ticker.bid_qty,
+                            ask: ticker.ask,
+                            ask_volume: ticker.ask_qty,
+                            last: ticker.last,
+                            volume_24h: ticker.volume,
+                            vwap_24h: ticker.vwap,
+                            low_24h: ticker.low,
+                            high_24h: ticker.high,
+                            open_24h: Some(ticker.last - ticker.change),
+                            trades_24h: None,
+                            timestamp: received_at,
+                        }));
+                    }
+                }
+                messages
+            }
+            Frame::Channel(ChannelFrame::Ohlc { data }) => data
+                .into_iter()
+                .map(|candle| {
+                    FeedMessage::Candle(Candle {
+                        symbol: from_v2_symbol(&candle.symbol),
+                        interval: candle.interval,
+                        start: candle.interval_begin,
+                        open: candle.open,
+                        high: candle.high,
+                        low: candle.low,
+                        close: candle.close,
+                        vwap: candle.vwap,
+                        volume: candle.volume,
+                        trades: candle.trades,
+                    })
+                })
+                .collect(),
+            Frame::Channel(ChannelFrame::Instrument { data }) => {
+                for pair in data.pairs {
+                    self.precisions.insert(
+                        pair.symbol,
+                        Precision {
+                            price: pair.price_precision,
+                            qty: pair.qty_precision,
+                        },
+                    );
+                }
+                Vec::new()
+            }
+            Frame::Channel(ChannelFrame::Status { data }) => {
+                tracing::info!("Kraken system status: {:?}", data);
+                Vec::new()
+            }
+            Frame::Channel(ChannelFrame::Heartbeat) => vec![FeedMessage::Heartbeat],
+            Frame::Channel(ChannelFrame::Other) => Vec::new(),
+            Frame::Method(response) => {
+                if response.method == "pong" {
+                    tracing::trace!("Kraken pong");
+                } else if response.success {
+                    tracing::info!("Kraken {} succeeded: {:?}", response.method, response.result);
+                    if let Some(MethodResult { channel, symbol: Some(symbol) }) = response.result {
+                        if response.method == "subscribe" && channel.as_deref() == Some("book") {
+                            return vec![FeedMessage::SubscriptionStatus {
+                                symbol: from_v2_symbol(&symbol),
+                                status: SubscriptionStatus::Subscribed,
+                            }];
+                        }
+                    }
+                } else {
+                    let reason = response.error.unwrap_or_default();
+                    tracing::warn!("Kraken {} failed: {}", response.method, reason);
+                    // A pair that is rejected on any channel is unusable
+                    if let (true, Some(symbol)) = (response.method == "subscribe", response.symbol) {
+                        return vec![FeedMessage::SubscriptionStatus {
+                            symbol: from_v2_symbol(&symbol),
+                            status: SubscriptionStatus::Rejected { reason },
+                        }];
+                    }
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    fn subscribe_symbol(&mut self, sub: &BookSubscription, config: &FeedConfig) -> Vec<String> {
+        self.depths.insert(sub.pair.to_string(), sub.depth);
+        self.pending.expect(&sub.pair.to_string());
+        let symbol = sub.pair.v2_name();
+
+        let mut frames = vec![book_request("subscribe", vec![symbol.clone()], sub.depth)];
+        frames.extend(self.channel_requests("subscribe", vec![symbol], config));
+        frames
+    }
+
+    fn unsubscribe_symbol(&mut self, symbol: &str, config: &FeedConfig) -> Vec<String> {
+        self.orderbooks.remove(symbol);
+        self.pending.forget(symbol);
+        let depth = self.depths.remove(symbol).unwrap_or(DEFAULT_BOOK_DEPTH);
+        let v2_symbol = to_v2_symbol(symbol);
+
+        let mut frames = vec![book_request("unsubscribe", vec![v2_symbol.clone()], depth)];
+        frames.extend(self.channel_requests("unsubscribe", vec![v2_symbol], config));
+        frames
+    }
+
+    fn resubscribe(&mut self, symbol: &str) -> Vec<String> {
+        self.orderbooks.remove(symbol);
+        self.pending.expect(symbol);
+        let depth = self.depths.get(symbol).copied().unwrap_or(DEFAULT_BOOK_DEPTH);
+
+        ["unsubscribe", "subscribe"]
+            .into_iter()
+            .map(|method| book_request(method, vec![to_v2_symbol(symbol)], depth))
+            .collect()
+    }
+
+    fn ping_frame(&mut self) -> Option<String> {
+        self.ping_id += 1;
+        Some(serde_json::json!({ "method": "ping", "req_id": self.ping_id }).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSTRUMENT: &str = include_str!("../../tests/fixtures/kraken_v2/instrument_snapshot.json");
+    const BOOK_SNAPSHOT: &str = include_str!("../../tests/fixtures/kraken_v2/book_snapshot.json");
+    const BOOK_UPDATE: &str = include_str!("../../tests/fixtures/kraken_v2/book_update.json");
+    const BOOK_UPDATE_BAD_CHECKSUM: &str =
+        include_str!("../../tests/fixtures/kraken_v2/book_update_bad_checksum.json");
+    const TRADE_UPDATE: &str = include_str!("../../tests/fixtures/kraken_v2/trade_update.json");
+    const TICKER_UPDATE: &str = include_str!("../../tests/fixtures/kraken_v2/ticker_update.json");
+    const OHLC_UPDATE: &str = include_str!("../../tests/fixtures/kraken_v2/ohlc_update.json");
+
+    fn config() -> FeedConfig {
+        FeedConfig {
+            subscriptions: vec![BookSubscription::new("XBT/USD", 10).unwrap()],
+            ..Default::default()
+        }
+    }
+
+    fn subscribed_session() -> V2Session {
+        let mut session = V2Session::new();
+        session.subscribe_frames(&config());
+        assert!(handle_text(&mut session, INSTRUMENT).is_empty());
+        session
+    }
+
+    /// Parse a text frame received just now
+    fn handle_text(session: &mut impl ExchangeAdapter, text: &str) -> Vec<FeedMessage> {
+        session.handle_frame(text, Utc::now())
+    }
+
+    fn expect_snapshot(mut messages: Vec<FeedMessage>) -> crate::storage::OrderbookSnapshot {
+        assert_eq!(messages.len(), 1);
+        match messages.remove(0) {
+            FeedMessage::Snapshot(snapshot) => snapshot,
+            other => panic!("expected a book snapshot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn subscribes_with_v2_symbols() {
+        let mut session = V2Session::new();
+        let frames = session.subscribe_frames(&config());
+
+        assert_eq!(frames.len(), 5);
+        assert!(frames[0].contains(r#""channel":"instrument""#));
+        let book: serde_json::Value = serde_json::from_str(&frames[1]).unwrap();
+        assert_eq!(book["method"], "subscribe");
+        assert_eq!(book["params"]["symbol"][0], "BTC/USD");
+        assert_eq!(book["params"]["depth"], 10);
+        let trade: serde_json::Value = serde_json::from_str(&frames[2]).unwrap();
+        assert_eq!(trade["params"]["channel"], "trade");
+        assert_eq!(trade["params"]["symbol"][0], "BTC/USD");
+        assert!(frames[3].contains(r#""channel":"ticker""#));
+        let ohlc: serde_json::Value = serde_json::from_str(&frames[4]).unwrap();
+        assert_eq!(ohlc["params"]["channel"], "ohlc");
+        assert_eq!(ohlc["params"]["interval"], 1);
+    }
+
+    #[test]
+    fn parses_ticker_into_ticker_and_spread() {
+        let mut session = subscribed_session();
+        let messages = handle_text(&mut session, TICKER_UPDATE);
+
+        let [FeedMessage::Spread(spread), FeedMessage::Ticker(ticker)] = messages.as_slice() else {
+            panic!("expected spread and ticker, got {:?}", messages);
+        };
+        assert_eq!(spread.symbol, "XBT/USD");
+        assert_eq!(spread.bid.to_string(), "45283.5");
+        assert_eq!(spread.ask.to_string(), "45285.2");
+        assert_eq!(ticker.last.to_string(), "45285.2");
+        assert_eq!(ticker.volume_24h.to_string(), "1723.47523131");
+        assert_eq!(ticker.open_24h.map(|o| o.to_string()), Some("45185.2".to_string()));
+    }
+
+    #[test]
+    fn parses_ohlc() {
+        let mut session = subscribed_session();
+        let messages = handle_text(&mut session, OHLC_UPDATE);
+
+        let [FeedMessage::Candle(candle)] = messages.as_slice() else {
+            panic!("expected one candle, got {:?}", messages);
+        };
+        assert_eq!(candle.symbol, "XBT/USD");
+        assert_eq!(candle.interval, 1);
+        assert_eq!(candle.open.to_string(), "45280.1");
+        assert_eq!(candle.close.to_string(), "45285.2");
+        assert_eq!(candle.trades, 12);
+        assert_eq!(
+            candle.start,
+            "2023-10-06T17:35:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_trades() {
+        let mut session = subscribed_session();
+        let messages = handle_text(&mut session, TRADE_UPDATE);
+
+        let [FeedMessage::Trades(trades)] = messages.as_slice() else {
+            panic!("expected one trade message");
+        };
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].symbol, "XBT/USD");
+        assert_eq!(trades[0].price.to_string(), "45285.2");
+        assert_eq!(trades[0].volume.to_string(), "0.00100000");
+        assert_eq!(trades[0].side, TradeSide::Buy);
+        assert_eq!(trades[0].order_type, TradeOrderType::Market);
+        assert_eq!(trades[0].trade_id, Some(62451981));
+        assert_eq!(trades[1].side, TradeSide::Sell);
+        assert_eq!(trades[1].order_type, TradeOrderType::Limit);
+    }
+
+    #[test]
+    fn parses_book_snapshot_and_verifies_checksum() {
+        let mut session = subscribed_session();
+        let snapshot = expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT));
+
+        assert_eq!(snapshot.symbol, "XBT/USD");
+        assert_eq!(snapshot.bids.len(), 10);
+        assert_eq!(snapshot.asks.len(), 10);
+        assert_eq!(snapshot.bids[0].price.to_string(), "45283.5");
+        assert_eq!(snapshot.bids[0].volume.to_string(), "0.10000000");
+        assert_eq!(snapshot.asks[0].price.to_string(), "45285.2");
+        assert_eq!(snapshot.checksum, Some(3310070434));
+    }
+
+    #[test]
+    fn keeps_numbers_beyond_float_precision() {
+        let mut session = subscribed_session();
+        let snapshot = expect_snapshot(handle_text(
+            &mut session,
+            r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":45283.5,"qty":12345678901.12345678}],"asks":[{"price":45285.2,"qty":0.00000001}]}]}"#,
+        ));
+
+        assert_eq!(snapshot.bids[0].volume.to_string(), "12345678901.12345678");
+        assert_eq!(snapshot.asks[0].volume.to_string(), "0.00000001");
+    }
+
+    #[test]
+    fn applies_book_update() {
+        let mut session = subscribed_session();
+        expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT));
+        let snapshot = expect_snapshot(handle_text(&mut session, BOOK_UPDATE));
+
+        // Best bid removed, a new ask inserted ahead of the old best
+        assert_eq!(snapshot.bids[0].price.to_string(), "45283.4");
+        assert_eq!(snapshot.asks[0].price.to_string(), "45284.9");
+        assert_eq!(snapshot.asks[0].volume.to_string(), "0.25000000");
+        assert_eq!(snapshot.bids.len(), 9);
+        assert_eq!(snapshot.asks.len(), 10);
+        assert_eq!(
+            snapshot.timestamp,
+            "2023-10-06T17:35:55.440295Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert!(snapshot.checksum.is_some());
+        assert_eq!(snapshot.sequence, Some(1));
+    }
+
+    #[test]
+    fn refuses_updates_before_snapshot() {
+        // Leftovers of a previous subscription are dropped while the snapshot is pending
+        let mut session = subscribed_session();
+        assert!(handle_text(&mut session, BOOK_UPDATE).is_empty());
+
+        let mut session = V2Session::new();
+        assert!(matches!(
+            handle_text(&mut session, BOOK_UPDATE).as_slice(),
+            [FeedMessage::InvalidBook { symbol, reason: BookError::MissingSnapshot }] if symbol == "XBT/USD"
+        ));
+    }
+
+    #[test]
+    fn reports_crossed_books() {
+        let mut session = subscribed_session();
+        assert_eq!(expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT)).sequence, Some(0));
+        let crossing_bid = r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":45290.0,"qty":1.0}],"asks":[],"timestamp":"2023-10-06T17:35:55.440295Z"}]}"#;
+
+        assert!(matches!(
+            handle_text(&mut session, crossing_bid).as_slice(),
+            [FeedMessage::InvalidBook { reason: BookError::Crossed { .. }, .. }]
+        ));
+        session.resubscribe("XBT/USD");
+        assert!(handle_text(&mut session, BOOK_UPDATE).is_empty(), "the dropped book is not updated");
+        assert_eq!(expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT)).sequence, Some(0));
+    }
+
+    #[test]
+    fn reports_checksum_mismatch() {
+        let mut session = subscribed_session();
+        expect_snapshot(handle_text(&mut session, BOOK_SNAPSHOT));
+        let messages = handle_text(&mut session, BOOK_UPDATE_BAD_CHECKSUM);
+
+        assert!(matches!(
+            messages.as_slice(),
+            [FeedMessage::ChecksumMismatch { symbol, expected: 1, .. }] if symbol == "XBT/USD"
+        ));
+    }
+
+    #[test]
+    fn logs_unrecognized_multibyte_frames() {
+        // Byte 200 falls inside a two-byte character
+        let text = format!("x{}", "é".repeat(150));
+        assert!(handle_text(&mut V2Session::new(), &text).is_empty());
+    }
+
+    #[test]
+    fn reports_heartbeats() {
+        let mut session = subscribed_session();
+        assert!(matches!(
+            handle_text(&mut session, r#"{"channel":"heartbeat"}"#).as_slice(),
+            [FeedMessage::Heartbeat]
+        ));
+    }
+
+    #[test]
+    fn ignores_control_frames() {
+        let mut session = subscribed_session();
+        assert!(handle_text(
+            &mut session,
+            r#"{"method":"pong","req_id":1,"time_in":"2023-09-25T09:04:31.742599Z","time_out":"2023-09-25T09:04:31.742648Z"}"#,
+        )
+        .is_empty());
+        assert!(handle_text(
+            &mut session,
+            r#"{"method":"subscribe","success":false,"error":"Currency pair not supported"}"#,
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn reports_subscription_status() {
+        let mut session = subscribed_session();
+
+        let accepted = handle_text(
+            &mut session,
+            r#"{"method":"subscribe","result":{"channel":"book","depth":10,"snapshot":true,"symbol":"BTC/USD"},"success":true,"time_in":"2023-09-25T09:04:31.742599Z","time_out":"2023-09-25T09:04:31.742648Z"}"#,
+        );
+        assert!(matches!(
+            accepted.as_slice(),
+            [FeedMessage::SubscriptionStatus { symbol, status: SubscriptionStatus::Subscribed }] if symbol == "XBT/USD"
+        ));
+
+        let rejected = handle_text(
+            &mut session,
+            r#"{"error":"Currency pair not supported BTC/USDX","method":"subscribe","success":false,"symbol":"BTC/USDX","time_in":"2023-09-25T09:04:31.742599Z","time_out":"2023-09-25T09:04:31.742648Z"}"#,
+        );
+        assert!(matches!(
+            rejected.as_slice(),
+            [FeedMessage::SubscriptionStatus { symbol, status: SubscriptionStatus::Rejected { reason } }]
+                if symbol == "XBT/USDX" && reason.contains("not supported")
+        ));
+    }
+}
@@ -0,0 +1,14 @@
+//! Orderbook Visualizer backend library
+
+pub mod api;
+pub mod consolidated;
+pub mod delta;
+pub mod exchange;
+pub mod feed;
+pub mod kraken_client;
+pub mod market_data;
+pub mod orderbook_manager;
+pub mod storage;
+pub mod symbols;
+pub mod synthetic;
+pub mod trading;
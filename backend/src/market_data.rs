@@ -0,0 +1,58 @@
+//! Market data models for the Kraken ticker, spread and OHLC channels
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// OHLC intervals (in minutes) supported by Kraken
+pub const SUPPORTED_OHLC_INTERVALS: [u32; 9] = [1, 5, 15, 30, 60, 240, 1440, 10080, 21600];
+
+/// Best quotes and rolling 24h statistics for a pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticker {
+    pub symbol: String,
+    pub bid: Decimal,
+    pub bid_volume: Decimal,
+    pub ask: Decimal,
+    pub ask_volume: Decimal,
+    /// Price of the last trade
+    pub last: Decimal,
+    pub volume_24h: Decimal,
+    pub vwap_24h: Decimal,
+    pub low_24h: Decimal,
+    pub high_24h: Decimal,
+    /// Opening price 24h ago (if available)
+    pub open_24h: Option<Decimal>,
+    /// Number of trades in the last 24h (if available)
+    pub trades_24h: Option<u64>,
+    /// Local time the ticker was received
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Best bid and offer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spread {
+    pub symbol: String,
+    pub bid: Decimal,
+    pub ask: Decimal,
+    pub bid_volume: Decimal,
+    pub ask_volume: Decimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// OHLC candle; updated in place until its interval closes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    pub symbol: String,
+    /// Interval length in minutes
+    pub interval: u32,
+    /// Start of the interval
+    pub start: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub vwap: Decimal,
+    pub volume: Decimal,
+    pub trades: u64,
+}
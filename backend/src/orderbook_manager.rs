@@ -1,35 +1,329 @@
 //! Orderbook state management and time-travel functionality
 
-use crate::storage::{OrderbookSnapshot, OrderbookStorage};
+use crate::consolidated::ConsolidatedBook;
+use crate::delta::BookDelta;
+use crate::exchange::Venue;
+use crate::feed::{BookError, OrderbookCallback, SubscriptionStatus};
+use crate::market_data::{Candle, Spread, Ticker};
+use crate::storage::{OrderbookSnapshot, OrderbookStorage, StorageStats, Trade};
+use crate::symbols::SymbolRegistry;
+use crate::synthetic::{SyntheticPair, DEFAULT_PRICE_DECIMALS};
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
 
 /// Orderbook manager with real-time updates and time-travel
 pub struct OrderbookManager {
     storage: Arc<OrderbookStorage>,
-    current_books: Arc<Mutex<HashMap<String, OrderbookSnapshot>>>,
-    update_tx: broadcast::Sender<OrderbookSnapshot>,
+    /// Latest book per venue and symbol
+    current_books: Arc<Mutex<HashMap<(Venue, String), LiveBook>>>,
+    /// Changes to the current books of each symbol across venues, in per-book
+    /// sequence order; created when a client first subscribes
+    update_channels: Arc<Mutex<HashMap<String, broadcast::Sender<BookDelta>>>>,
+    trade_tx: broadcast::Sender<Trade>,
+    feed_stats: Arc<Mutex<HashMap<String, FeedStats>>>,
+    tickers: Arc<Mutex<HashMap<String, Ticker>>>,
+    spreads: Arc<Mutex<HashMap<String, Spread>>>,
+    candles: Arc<Mutex<HashMap<String, VecDeque<Candle>>>>,
+    connections: Arc<Mutex<FeedConnections>>,
+    subscriptions: Arc<Mutex<HashMap<String, SubscriptionStatus>>>,
+    subscription_tx: broadcast::Sender<SymbolSubscription>,
+    symbols: Arc<SymbolRegistry>,
+    /// Pairs implied from two Kraken books, recomputed whenever a leg changes
+    synthetics: Arc<Mutex<Vec<SyntheticPair>>>,
+}
+
+/// Deltas buffered per symbol before slow subscribers lag
+const UPDATE_CHANNEL_CAPACITY: usize = 256;
+
+/// Current book with the sequence of the last delta broadcast for it
+#[derive(Debug, Clone)]
+pub struct LiveBook {
+    pub snapshot: OrderbookSnapshot,
+    /// A stream starting from this book continues with the delta after this one
+    pub sequence: u64,
+}
+
+/// Replace the current book of a venue and symbol, returning the delta to broadcast
+fn publish(current: &mut HashMap<(Venue, String), LiveBook>, snapshot: OrderbookSnapshot) -> BookDelta {
+    match current.get_mut(&(snapshot.venue, snapshot.symbol.clone())) {
+        Some(live) => {
+            live.sequence += 1;
+            let delta = BookDelta::between(Some(&live.snapshot), &snapshot, live.sequence);
+            live.snapshot = snapshot;
+            delta
+        }
+        None => {
+            let delta = BookDelta::between(None, &snapshot, 1);
+            current.insert((snapshot.venue, snapshot.symbol.clone()), LiveBook { snapshot, sequence: 1 });
+            delta
+        }
+    }
+}
+
+/// Drop the current book of a venue and symbol, returning a delta that empties it.
+///
+/// The delta is a stale reset, so streaming clients stop showing the book
+/// instead of freezing on its last levels.
+fn retire(current: &mut HashMap<(Venue, String), LiveBook>, venue: Venue, symbol: &str) -> Option<BookDelta> {
+    let live = current.remove(&(venue, symbol.to_string()))?;
+    let empty = OrderbookSnapshot {
+        bids: Vec::new(),
+        asks: Vec::new(),
+        checksum: None,
+        stale: true,
+        ..live.snapshot
+    };
+    Some(BookDelta::between(None, &empty, live.sequence + 1))
+}
+
+/// Subscription status change for a symbol
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolSubscription {
+    pub symbol: String,
+    pub status: SubscriptionStatus,
+}
+
+/// Number of candles kept in memory per symbol
+const MAX_CANDLES: usize = 720;
+
+/// Weight given to the newest sample in the latency moving average
+const LATENCY_EWMA_ALPHA: f64 = 0.1;
+
+/// Feed lag for a symbol: local receive time minus exchange time
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    pub samples: u64,
+    pub last_ms: f64,
+    /// Exponentially weighted moving average
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyStats {
+    fn record(&mut self, latency_ms: f64) {
+        self.avg_ms = if self.samples == 0 {
+            latency_ms
+        } else {
+            LATENCY_EWMA_ALPHA * latency_ms + (1.0 - LATENCY_EWMA_ALPHA) * self.avg_ms
+        };
+        self.last_ms = latency_ms;
+        self.max_ms = self.max_ms.max(latency_ms);
+        self.samples += 1;
+    }
+}
+
+/// Feed health counters tracked per symbol since startup
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeedStats {
+    /// Number of book checksum mismatches detected
+    pub checksum_mismatches: u64,
+    /// Number of books dropped for breaking an invariant (e.g. crossed, or updated before the snapshot)
+    pub invalid_books: u64,
+    pub latency: LatencyStats,
+}
+
+/// Per-symbol statistics combining storage and feed health
+#[derive(Debug, Serialize)]
+pub struct SymbolStats {
+    #[serde(flatten)]
+    pub storage: StorageStats,
+    #[serde(flatten)]
+    pub feed: FeedStats,
+}
+
+/// State of the exchange feed connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+/// Lifecycle bookkeeping for one feed connection
+#[derive(Debug)]
+struct ConnectionTracker {
+    state: ConnectionState,
+    connected_since: Option<DateTime<Utc>>,
+    /// Connections established after the first one
+    reconnects: u64,
+    last_disconnect: Option<DateTime<Utc>>,
+}
+
+/// Bookkeeping for every connection of the feed
+#[derive(Debug, Default)]
+struct FeedConnections {
+    /// Keyed by venue and connection id; a connection appears once it first connects
+    connections: BTreeMap<(Venue, usize), ConnectionTracker>,
+    last_error: Option<String>,
+    last_error_at: Option<DateTime<Utc>>,
+}
+
+/// Connection health reported by the API.
+///
+/// The top-level fields summarize every connection: the feed is `connected`
+/// only while all of them are up, and `reconnects` is their total.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    /// Since when every connection has been up
+    pub connected_since: Option<DateTime<Utc>>,
+    /// Seconds every connection has been up
+    pub uptime_secs: Option<i64>,
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub last_disconnect: Option<DateTime<Utc>>,
+    pub connections: Vec<FeedConnectionStatus>,
+}
+
+/// Health of a single connection of the feed
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedConnectionStatus {
+    pub venue: Venue,
+    pub id: usize,
+    pub state: ConnectionState,
+    pub connected_since: Option<DateTime<Utc>>,
+    pub uptime_secs: Option<i64>,
+    pub reconnects: u64,
+    pub last_disconnect: Option<DateTime<Utc>>,
 }
 
 impl OrderbookManager {
-    /// Create a new orderbook manager
+    /// Create a new orderbook manager that knows the built-in pairs
     pub fn new(storage_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_symbols(storage_path, Arc::new(SymbolRegistry::builtin()))
+    }
+
+    /// Create a new orderbook manager resolving pair names with `symbols`
+    pub fn with_symbols(storage_path: &str, symbols: Arc<SymbolRegistry>) -> Result<Self, Box<dyn std::error::Error>> {
         let storage = Arc::new(OrderbookStorage::new(storage_path)?);
         let current_books = Arc::new(Mutex::new(HashMap::new()));
-        let (update_tx, _) = broadcast::channel(1000);
+        let (trade_tx, _) = broadcast::channel(1000);
+        let (subscription_tx, _) = broadcast::channel(100);
 
         Ok(Self {
             storage,
             current_books,
-            update_tx,
+            update_channels: Arc::new(Mutex::new(HashMap::new())),
+            trade_tx,
+            feed_stats: Arc::new(Mutex::new(HashMap::new())),
+            tickers: Arc::new(Mutex::new(HashMap::new())),
+            spreads: Arc::new(Mutex::new(HashMap::new())),
+            candles: Arc::new(Mutex::new(HashMap::new())),
+            connections: Arc::new(Mutex::new(FeedConnections::default())),
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            subscription_tx,
+            symbols,
+            synthetics: Arc::new(Mutex::new(Vec::new())),
         })
     }
 
-    /// Get the current orderbook for a symbol
+    /// Registry used to map pair names to canonical symbols
+    pub fn symbols(&self) -> &SymbolRegistry {
+        &self.symbols
+    }
+
+    /// Get the current Kraken orderbook for a symbol
     pub fn get_current(&self, symbol: &str) -> Option<OrderbookSnapshot> {
-        self.current_books.lock().unwrap().get(symbol).cloned()
+        self.get_venue_book(Venue::Kraken, symbol)
+    }
+
+    /// Get the current orderbook for a symbol on `venue`
+    pub fn get_venue_book(&self, venue: Venue, symbol: &str) -> Option<OrderbookSnapshot> {
+        self.get_live_book(venue, symbol).map(|live| live.snapshot)
+    }
+
+    /// Get the current orderbook for a symbol on `venue` with its delta sequence, to start a stream from
+    pub fn get_live_book(&self, venue: Venue, symbol: &str) -> Option<LiveBook> {
+        self.current_books
+            .lock()
+            .unwrap()
+            .get(&(venue, symbol.to_string()))
+            .cloned()
+    }
+
+    /// Venue served when a request does not name one: the implied book for synthetic pairs, Kraken otherwise
+    pub fn default_venue(&self, symbol: &str) -> Venue {
+        let synthetics = self.synthetics.lock().unwrap();
+        if synthetics.iter().any(|pair| pair.symbol == symbol) {
+            Venue::Synthetic
+        } else {
+            Venue::Kraken
+        }
+    }
+
+    /// Start implying a synthetic pair from its legs, replacing any earlier definition
+    pub fn add_synthetic(&self, pair: SyntheticPair) {
+        let legs = [pair.base_leg().to_string(), pair.quote_leg().to_string()];
+        {
+            let mut synthetics = self.synthetics.lock().unwrap();
+            synthetics.retain(|existing| existing.symbol != pair.symbol);
+            synthetics.push(pair);
+        }
+
+        // Imply it right away if both legs already have books
+        let implied = {
+            let mut current = self.current_books.lock().unwrap();
+            self.refresh_synthetics(&mut current, &legs)
+        };
+        self.broadcast(implied);
+        tracing::info!("Implying synthetic books from {} and {}", legs[0], legs[1]);
+    }
+
+    /// Configured synthetic pairs
+    pub fn synthetics(&self) -> Vec<SyntheticPair> {
+        self.synthetics.lock().unwrap().clone()
+    }
+
+    /// Recompute the synthetic books implied from the Kraken books of `legs`.
+    ///
+    /// Synthetic books lose their entry while either leg has no book. Returns
+    /// the deltas, including those retiring dropped books, for broadcasting
+    /// once the lock is released.
+    fn refresh_synthetics(
+        &self,
+        current: &mut HashMap<(Venue, String), LiveBook>,
+        legs: &[String],
+    ) -> Vec<BookDelta> {
+        let synthetics = self.synthetics.lock().unwrap();
+        let mut implied = Vec::new();
+
+        for pair in synthetics.iter().filter(|pair| legs.iter().any(|leg| pair.uses(leg))) {
+            let base_leg = current.get(&(Venue::Kraken, pair.base_leg().to_string()));
+            let quote_leg = current.get(&(Venue::Kraken, pair.quote_leg().to_string()));
+            match (base_leg, quote_leg) {
+                (Some(base_leg), Some(quote_leg)) => {
+                    let price_decimals = self
+                        .symbols
+                        .info(&pair.symbol)
+                        .map_or(DEFAULT_PRICE_DECIMALS, |info| info.price_precision);
+                    let snapshot = pair.implied(&base_leg.snapshot, &quote_leg.snapshot, price_decimals);
+                    implied.push(publish(current, snapshot));
+                }
+                _ => implied.extend(retire(current, Venue::Synthetic, &pair.symbol.to_string())),
+            }
+        }
+        implied
+    }
+
+    /// Merge the current books of a symbol across every exchange tracking it.
+    ///
+    /// Implied books are left out: their levels are derived from other books,
+    /// not liquidity anyone can trade against.
+    pub fn get_consolidated(&self, symbol: &str) -> Option<ConsolidatedBook> {
+        let books: Vec<OrderbookSnapshot> = self
+            .current_books
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((venue, book_symbol), _)| *venue != Venue::Synthetic && book_symbol == symbol)
+            .map(|(_, live)| live.snapshot.clone())
+            .collect();
+        ConsolidatedBook::merge(symbol, &books)
     }
 
     /// Get orderbook history
@@ -60,33 +354,391 @@ impl OrderbookManager {
         Ok(snapshots.last().cloned())
     }
 
-    /// Subscribe to real-time updates
-    pub fn subscribe_updates(&self) -> broadcast::Receiver<OrderbookSnapshot> {
-        self.update_tx.subscribe()
+    /// Subscribe to changes of the current books of `symbol` on every venue
+    pub fn subscribe_updates(&self, symbol: &str) -> broadcast::Receiver<BookDelta> {
+        self.update_channels
+            .lock()
+            .unwrap()
+            .entry(symbol.to_string())
+            .or_insert_with(|| broadcast::channel(UPDATE_CHANNEL_CAPACITY).0)
+            .subscribe()
     }
 
-    /// Update orderbook state from snapshot
+    /// Send deltas to the subscribers of their symbol, dropping channels nobody listens to anymore
+    fn broadcast(&self, deltas: Vec<BookDelta>) {
+        let mut channels = self.update_channels.lock().unwrap();
+        for delta in deltas {
+            let symbol = delta.symbol.clone();
+            if let Some(tx) = channels.get(&symbol) {
+                if tx.send(delta).is_err() {
+                    channels.remove(&symbol);
+                }
+            }
+        }
+    }
+
+    /// Update orderbook state from snapshot.
+    ///
+    /// Books from every venue are kept, and their changes since the previous
+    /// book broadcast as a delta; latency statistics and history cover Kraken
+    /// books only, since both are keyed by symbol.
     pub fn update_orderbook_snapshot(&self, snapshot: OrderbookSnapshot) {
         let symbol = snapshot.symbol.clone();
+        let venue = snapshot.venue;
+        let is_kraken = venue == Venue::Kraken;
+
+        // Track feed lag when the exchange time is known. A book snapshot
+        // (sequence 0) is stamped with its newest level, which measures the
+        // book's age rather than lag, so only updates count.
+        if let Some(received_at) = snapshot.received_at.filter(|_| is_kraken && snapshot.sequence != Some(0)) {
+            if received_at != snapshot.timestamp {
+                let latency_ms = (received_at - snapshot.timestamp).num_microseconds().unwrap_or(0) as f64 / 1000.0;
+                let mut stats = self.feed_stats.lock().unwrap();
+                stats.entry(symbol.clone()).or_default().latency.record(latency_ms);
+            }
+        }
         
-        // Update current state
-        {
+        // Store snapshot (throttle to avoid too many writes)
+        if is_kraken {
+            if let Err(e) = self.storage.store_snapshot(&snapshot) {
+                tracing::error!("Failed to store snapshot: {}", e);
+            }
+        }
+
+        // Update current state, and the synthetic books using it as a leg
+        let deltas = {
             let mut current = self.current_books.lock().unwrap();
-            current.insert(symbol.clone(), snapshot.clone());
+            let mut deltas = vec![publish(&mut current, snapshot)];
             tracing::debug!("Stored snapshot for {}, total symbols: {}", symbol, current.len());
+            if is_kraken {
+                deltas.extend(self.refresh_synthetics(&mut current, std::slice::from_ref(&symbol)));
+            }
+            deltas
+        };
+
+        // Broadcast update
+        self.broadcast(deltas);
+    }
+
+    /// Get executed trades, keeping the most recent `limit` in the range
+    pub fn get_trades(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<Trade>, Box<dyn std::error::Error>> {
+        self.storage.get_trades(symbol, from, to, limit)
+    }
+
+    /// Subscribe to real-time trades
+    pub fn subscribe_trades(&self) -> broadcast::Receiver<Trade> {
+        self.trade_tx.subscribe()
+    }
+
+    /// Store and broadcast an executed trade
+    pub fn record_trade(&self, trade: Trade) {
+        if let Err(e) = self.storage.store_trade(&trade) {
+            tracing::error!("Failed to store trade: {}", e);
         }
 
-        // Store snapshot (throttle to avoid too many writes)
-        if let Err(e) = self.storage.store_snapshot(&snapshot) {
-            tracing::error!("Failed to store snapshot: {}", e);
+        let _ = self.trade_tx.send(trade);
+    }
+
+    /// Get the latest 24h ticker for a symbol
+    pub fn get_ticker(&self, symbol: &str) -> Option<Ticker> {
+        self.tickers.lock().unwrap().get(symbol).cloned()
+    }
+
+    /// Get the latest best bid/offer for a symbol
+    pub fn get_spread(&self, symbol: &str) -> Option<Spread> {
+        self.spreads.lock().unwrap().get(symbol).cloned()
+    }
+
+    /// Get the most recent `limit` candles for a symbol, oldest first
+    pub fn get_candles(&self, symbol: &str, limit: usize) -> Vec<Candle> {
+        self.candles
+            .lock()
+            .unwrap()
+            .get(symbol)
+            .map(|candles| candles.iter().skip(candles.len().saturating_sub(limit)).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Update the latest ticker
+    pub fn update_ticker(&self, ticker: Ticker) {
+        self.tickers.lock().unwrap().insert(ticker.symbol.clone(), ticker);
+    }
+
+    /// Update the latest best bid/offer
+    pub fn update_spread(&self, spread: Spread) {
+        self.spreads.lock().unwrap().insert(spread.symbol.clone(), spread);
+    }
+
+    /// Update a candle; the open candle is replaced until its interval closes
+    pub fn update_candle(&self, candle: Candle) {
+        let mut candles = self.candles.lock().unwrap();
+        let series = candles.entry(candle.symbol.clone()).or_default();
+
+        match series.back_mut() {
+            Some(last) if last.start == candle.start => *last = candle,
+            _ => {
+                series.push_back(candle);
+                if series.len() > MAX_CANDLES {
+                    series.pop_front();
+                }
+            }
         }
+    }
 
-        // Broadcast update
-        let _ = self.update_tx.send(snapshot);
+    /// Record that feed connection `connection` of `venue` is up
+    pub fn set_connected(&self, venue: Venue, connection: usize) {
+        let mut feed = self.connections.lock().unwrap();
+        // A connection is tracked from its first connect on, so a known one is reconnecting
+        let tracker = feed
+            .connections
+            .entry((venue, connection))
+            .and_modify(|tracker| tracker.reconnects += 1)
+            .or_insert(ConnectionTracker {
+                state: ConnectionState::Connecting,
+                connected_since: None,
+                reconnects: 0,
+                last_disconnect: None,
+            });
+        tracker.state = ConnectionState::Connected;
+        tracker.connected_since = Some(Utc::now());
+    }
+
+    /// Record that feed connection `connection` of `venue` dropped and mark the books of its `symbols` as stale.
+    ///
+    /// Books stay queryable; they are replaced by fresh snapshots once the
+    /// feed resubscribes. Books carried by other connections are unaffected.
+    pub fn set_disconnected(&self, venue: Venue, connection: usize, symbols: &[String]) {
+        if let Some(tracker) = self.connections.lock().unwrap().connections.get_mut(&(venue, connection)) {
+            tracker.state = ConnectionState::Disconnected;
+            tracker.connected_since = None;
+            tracker.last_disconnect = Some(Utc::now());
+        }
+
+        let stale: Vec<BookDelta> = {
+            let mut current = self.current_books.lock().unwrap();
+            let mut stale: Vec<BookDelta> = symbols
+                .iter()
+                .filter_map(|symbol| {
+                    let mut snapshot = current.get(&(venue, symbol.clone()))?.snapshot.clone();
+                    snapshot.stale = true;
+                    Some(publish(&mut current, snapshot))
+                })
+                .collect();
+            // Synthetic books go stale with their legs
+            if venue == Venue::Kraken {
+                stale.extend(self.refresh_synthetics(&mut current, symbols));
+            }
+            stale
+        };
+
+        // Let streaming clients know their books are no longer live
+        self.broadcast(stale);
+    }
+
+    /// Record a feed connection error
+    pub fn record_connection_error(&self, error: String) {
+        let mut feed = self.connections.lock().unwrap();
+        feed.last_error = Some(error);
+        feed.last_error_at = Some(Utc::now());
+    }
+
+    /// Get the feed connection status, summarized and per connection
+    pub fn connection_status(&self) -> ConnectionStatus {
+        let feed = self.connections.lock().unwrap();
+        let now = Utc::now();
+        let connections: Vec<FeedConnectionStatus> = feed
+            .connections
+            .iter()
+            .map(|(&(venue, id), tracker)| FeedConnectionStatus {
+                venue,
+                id,
+                state: tracker.state,
+                connected_since: tracker.connected_since,
+                uptime_secs: tracker.connected_since.map(|since| (now - since).num_seconds()),
+                reconnects: tracker.reconnects,
+                last_disconnect: tracker.last_disconnect,
+            })
+            .collect();
+
+        let state = if connections.is_empty() {
+            ConnectionState::Connecting
+        } else if connections.iter().all(|c| c.state == ConnectionState::Connected) {
+            ConnectionState::Connected
+        } else {
+            ConnectionState::Disconnected
+        };
+        let connected_since = match state {
+            ConnectionState::Connected => connections.iter().filter_map(|c| c.connected_since).max(),
+            _ => None,
+        };
+
+        ConnectionStatus {
+            state,
+            connected_since,
+            uptime_secs: connected_since.map(|since| (now - since).num_seconds()),
+            reconnects: connections.iter().map(|c| c.reconnects).sum(),
+            last_error: feed.last_error.clone(),
+            last_error_at: feed.last_error_at,
+            last_disconnect: connections.iter().filter_map(|c| c.last_disconnect).max(),
+            connections,
+        }
+    }
+
+    /// Get the subscription status of a symbol, if it is tracked
+    pub fn get_subscription_status(&self, symbol: &str) -> Option<SubscriptionStatus> {
+        self.subscriptions.lock().unwrap().get(symbol).cloned()
+    }
+
+    /// Subscribe to subscription status changes
+    pub fn subscribe_subscription_status(&self) -> broadcast::Receiver<SymbolSubscription> {
+        self.subscription_tx.subscribe()
+    }
+
+    /// Update the subscription status of a symbol
+    pub fn set_subscription_status(&self, symbol: &str, status: SubscriptionStatus) {
+        let previous = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .insert(symbol.to_string(), status.clone());
+
+        if previous.as_ref() != Some(&status) {
+            let _ = self.subscription_tx.send(SymbolSubscription {
+                symbol: symbol.to_string(),
+                status,
+            });
+        }
     }
 
-    /// Get storage for a symbol
-    pub fn get_stats(&self, symbol: &str) -> Result<crate::storage::StorageStats, Box<dyn std::error::Error>> {
-        self.storage.get_stats(symbol)
+    /// Forget the live state of a symbol that is no longer tracked; history is kept
+    pub fn remove_symbol(&self, symbol: &str) {
+        self.subscriptions.lock().unwrap().remove(symbol);
+        let retired = {
+            let mut current = self.current_books.lock().unwrap();
+            let mut retired: Vec<BookDelta> = retire(&mut current, Venue::Kraken, symbol).into_iter().collect();
+            // Drops the synthetic books this was a leg of
+            retired.extend(self.refresh_synthetics(&mut current, &[symbol.to_string()]));
+            retired
+        };
+        self.broadcast(retired);
+        self.tickers.lock().unwrap().remove(symbol);
+        self.spreads.lock().unwrap().remove(symbol);
+        self.candles.lock().unwrap().remove(symbol);
+    }
+
+    /// Record a checksum mismatch reported by the feed
+    pub fn record_checksum_mismatch(&self, symbol: &str) {
+        let mut stats = self.feed_stats.lock().unwrap();
+        stats.entry(symbol.to_string()).or_default().checksum_mismatches += 1;
+    }
+
+    /// Record a book the feed dropped for breaking an invariant
+    pub fn record_invalid_book(&self, symbol: &str) {
+        let mut stats = self.feed_stats.lock().unwrap();
+        stats.entry(symbol.to_string()).or_default().invalid_books += 1;
+    }
+
+    /// Get storage and feed statistics for a symbol
+    pub fn get_stats(&self, symbol: &str) -> Result<SymbolStats, Box<dyn std::error::Error>> {
+        let storage = self.storage.get_stats(symbol)?;
+        let feed = self
+            .feed_stats
+            .lock()
+            .unwrap()
+            .get(symbol)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(SymbolStats { storage, feed })
+    }
+}
+
+/// Callback that feeds one venue's updates to the manager.
+///
+/// Subscription status and feed statistics are only tracked for Kraken, whose
+/// pairs are managed through the admin API; other venues contribute books.
+pub struct ManagerCallback {
+    pub manager: Arc<OrderbookManager>,
+    pub venue: Venue,
+}
+
+impl ManagerCallback {
+    /// Callback for the Kraken feed
+    pub fn new(manager: Arc<OrderbookManager>) -> Self {
+        Self {
+            manager,
+            venue: Venue::Kraken,
+        }
+    }
+}
+
+impl OrderbookCallback for ManagerCallback {
+    fn on_orderbook(&self, snapshot: OrderbookSnapshot) {
+        tracing::info!("Received {} orderbook update for {} with {} bids, {} asks",
+            snapshot.venue, snapshot.symbol, snapshot.bids.len(), snapshot.asks.len());
+        self.manager.update_orderbook_snapshot(snapshot);
+    }
+    
+    fn on_connected(&self, connection: usize) {
+        tracing::info!("{} WebSocket connection {} connected", self.venue, connection);
+        self.manager.set_connected(self.venue, connection);
+    }
+    
+    fn on_disconnected(&self, connection: usize, symbols: &[String]) {
+        tracing::warn!("{} WebSocket connection {} disconnected", self.venue, connection);
+        self.manager.set_disconnected(self.venue, connection, symbols);
+    }
+    
+    fn on_error(&self, error: String) {
+        tracing::error!("{} WebSocket error: {}", self.venue, error);
+        if self.venue == Venue::Kraken {
+            self.manager.record_connection_error(error);
+        } else {
+            self.manager.record_connection_error(format!("{}: {}", self.venue, error));
+        }
+    }
+
+    fn on_checksum_mismatch(&self, symbol: &str, expected: u32, computed: u32) {
+        tracing::warn!("Checksum mismatch for {} {}: expected {}, computed {}", self.venue, symbol, expected, computed);
+        if self.venue == Venue::Kraken {
+            self.manager.record_checksum_mismatch(symbol);
+        }
+    }
+
+    fn on_invalid_book(&self, symbol: &str, reason: &BookError) {
+        tracing::warn!("Invalid {} book for {}: {}", self.venue, symbol, reason);
+        if self.venue == Venue::Kraken {
+            self.manager.record_invalid_book(symbol);
+        }
+    }
+
+    fn on_trade(&self, trade: Trade) {
+        tracing::debug!("Trade {} {:?} {} @ {}", trade.symbol, trade.side, trade.volume, trade.price);
+        self.manager.record_trade(trade);
+    }
+
+    fn on_ticker(&self, ticker: Ticker) {
+        self.manager.update_ticker(ticker);
+    }
+
+    fn on_spread(&self, spread: Spread) {
+        self.manager.update_spread(spread);
+    }
+
+    fn on_candle(&self, candle: Candle) {
+        self.manager.update_candle(candle);
+    }
+
+    fn on_subscription_status(&self, symbol: &str, status: SubscriptionStatus) {
+        tracing::info!("{} subscription status for {}: {:?}", self.venue, symbol, status);
+        if self.venue == Venue::Kraken {
+            self.manager.set_subscription_status(symbol, status);
+        }
     }
 }
@@ -1,49 +1,124 @@
 //! Time-series storage for orderbook snapshots
 
+use crate::exchange::Venue;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sled::Db;
+use sled::{Db, Tree};
 use std::sync::Arc;
 
 /// Orderbook snapshot at a point in time
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderbookSnapshot {
+    /// Exchange the book comes from
+    #[serde(default)]
+    pub venue: Venue,
     pub symbol: String,
+    /// Exchange time of the latest update in the book (local receive time if unknown)
     pub timestamp: DateTime<Utc>,
+    /// Local time the message producing this snapshot was received
+    #[serde(default)]
+    pub received_at: Option<DateTime<Utc>>,
     pub bids: Vec<PriceLevel>,
     pub asks: Vec<PriceLevel>,
     pub checksum: Option<u32>,
     pub sequence: Option<u64>,
+    /// Set while the feed is disconnected and the book may be out of date
+    #[serde(default)]
+    pub stale: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl OrderbookSnapshot {
+    /// Keep at most `depth` levels on each side
+    pub fn truncate(&mut self, depth: usize) {
+        self.bids.truncate(depth);
+        self.asks.truncate(depth);
+    }
+
+    /// Copy with at most `depth` levels on each side, without cloning the rest
+    pub fn truncated(&self, depth: usize) -> Self {
+        Self {
+            venue: self.venue,
+            symbol: self.symbol.clone(),
+            timestamp: self.timestamp,
+            received_at: self.received_at,
+            bids: self.bids.iter().take(depth).cloned().collect(),
+            asks: self.asks.iter().take(depth).cloned().collect(),
+            checksum: self.checksum,
+            sequence: self.sequence,
+            stale: self.stale,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PriceLevel {
     pub price: Decimal,
     pub volume: Decimal,
     /// Number of orders at this price level (if available)
     pub order_count: Option<u32>,
+    /// Exchange time this level was last updated (if available)
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// Aggressor side of an executed trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+/// Type of the order that triggered a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeOrderType {
+    Market,
+    Limit,
+}
+
+/// Executed trade reported by the exchange
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub symbol: String,
+    pub price: Decimal,
+    pub volume: Decimal,
+    pub side: TradeSide,
+    pub order_type: TradeOrderType,
+    /// Exchange execution time
+    pub timestamp: DateTime<Utc>,
+    /// Exchange trade id (if available)
+    pub trade_id: Option<u64>,
 }
 
 /// Time-series storage for orderbook data
 pub struct OrderbookStorage {
     db: Arc<Db>,
+    trades: Tree,
 }
 
 impl OrderbookStorage {
     /// Create a new storage instance
     pub fn new(path: &str) -> Result<Self, sled::Error> {
         let db = sled::open(path)?;
-        Ok(Self { db: Arc::new(db) })
+        let trades = db.open_tree("trades")?;
+        Ok(Self {
+            db: Arc::new(db),
+            trades,
+        })
     }
 
     /// Store an orderbook snapshot
     pub fn store_snapshot(&self, snapshot: &OrderbookSnapshot) -> Result<(), Box<dyn std::error::Error>> {
-        // Key format: "symbol:timestamp_nanos"
+        // Key format: "symbol:timestamp_nanos:received_nanos"; exchange times
+        // repeat across messages (e.g. a resubscribe snapshot whose newest
+        // level is the last update), so the receive time keeps both
         let key = format!(
-            "{}:{}",
+            "{}:{}:{}",
             snapshot.symbol,
-            snapshot.timestamp.timestamp_nanos_opt().unwrap_or(0)
+            snapshot.timestamp.timestamp_nanos_opt().unwrap_or(0),
+            snapshot.received_at.and_then(|at| at.timestamp_nanos_opt()).unwrap_or(0)
         );
 
         let value = serde_json::to_vec(snapshot)?;
@@ -114,17 +189,17 @@ impl OrderbookStorage {
         symbol: &str,
         timestamp: DateTime<Utc>,
     ) -> Result<Option<OrderbookSnapshot>, Box<dyn std::error::Error>> {
-        let key = format!(
-            "{}:{}",
-            symbol,
-            timestamp.timestamp_nanos_opt().unwrap_or(0)
-        );
-
-        if let Some(value) = self.db.get(key.as_bytes())? {
-            let snapshot: OrderbookSnapshot = serde_json::from_slice(&value)?;
-            Ok(Some(snapshot))
-        } else {
-            Ok(None)
+        let key = format!("{}:{}", symbol, timestamp.timestamp_nanos_opt().unwrap_or(0));
+
+        // The last one received when several share the exchange time, or a
+        // snapshot stored before keys carried the receive time
+        let value = match self.db.scan_prefix(format!("{}:", key).as_bytes()).last().transpose()? {
+            Some((_key, value)) => Some(value),
+            None => self.db.get(key.as_bytes())?,
+        };
+        match value {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
         }
     }
 
@@ -166,6 +241,64 @@ impl OrderbookStorage {
         })
     }
 
+    /// Store an executed trade
+    pub fn store_trade(&self, trade: &Trade) -> Result<(), Box<dyn std::error::Error>> {
+        // Key format: "symbol:timestamp_nanos:seq"; trades without an exchange
+        // id take a database id, which keeps counting across restarts
+        let seq = match trade.trade_id {
+            Some(id) => id,
+            None => self.db.generate_id()?,
+        };
+        let key = format!(
+            "{}:{}:{}",
+            trade.symbol,
+            trade.timestamp.timestamp_nanos_opt().unwrap_or(0),
+            seq
+        );
+
+        let value = serde_json::to_vec(trade)?;
+        self.trades.insert(key.as_bytes(), value)?;
+
+        Ok(())
+    }
+
+    /// Get trades within a time range, oldest first, keeping the most recent `limit`
+    pub fn get_trades(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<Trade>, Box<dyn std::error::Error>> {
+        let prefix = format!("{}:", symbol);
+        let from_nanos = from.timestamp_nanos_opt().unwrap_or(0);
+        let to_nanos = to.timestamp_nanos_opt().unwrap_or(i64::MAX);
+
+        let mut trades = Vec::new();
+
+        for result in self.trades.scan_prefix(prefix.as_bytes()) {
+            let (key, value) = result?;
+            let key_str = String::from_utf8_lossy(&key);
+
+            if let Some(timestamp_str) = key_str.split(':').nth(1) {
+                if let Ok(timestamp_nanos) = timestamp_str.parse::<i64>() {
+                    if timestamp_nanos >= from_nanos && timestamp_nanos <= to_nanos {
+                        let trade: Trade = serde_json::from_slice(&value)?;
+                        trades.push(trade);
+                    }
+                }
+            }
+        }
+
+        // Sort by timestamp and keep the latest trades
+        trades.sort_by_key(|t| t.timestamp);
+        if trades.len() > limit {
+            trades.drain(..trades.len() - limit);
+        }
+
+        Ok(trades)
+    }
+
     /// Clear all data for a symbol
     pub fn clear_symbol(&self, symbol: &str) -> Result<(), Box<dyn std::error::Error>> {
         let prefix = format!("{}:", symbol);
@@ -180,6 +313,16 @@ impl OrderbookStorage {
             self.db.remove(key)?;
         }
 
+        let trade_keys: Vec<_> = self.trades
+            .scan_prefix(prefix.as_bytes())
+            .filter_map(|r| r.ok())
+            .map(|(key, _)| key)
+            .collect();
+
+        for key in trade_keys {
+            self.trades.remove(key)?;
+        }
+
         Ok(())
     }
 }
@@ -191,3 +334,77 @@ pub struct StorageStats {
     pub oldest_snapshot: Option<DateTime<Utc>>,
     pub newest_snapshot: Option<DateTime<Utc>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_snapshots_sharing_an_exchange_time() {
+        let path = std::env::temp_dir().join(format!("orderbook-storage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let storage = OrderbookStorage::new(path.to_str().unwrap()).unwrap();
+
+        let timestamp = Utc::now();
+        let snapshot = |sequence: u64, received_ms: i64| OrderbookSnapshot {
+            venue: Venue::Kraken,
+            symbol: "XBT/USD".to_string(),
+            timestamp,
+            received_at: Some(timestamp + chrono::Duration::milliseconds(received_ms)),
+            bids: Vec::new(),
+            asks: Vec::new(),
+            checksum: None,
+            sequence: Some(sequence),
+            stale: false,
+        };
+        // A resubscribe snapshot whose newest level is the last update
+        storage.store_snapshot(&snapshot(7, 5)).unwrap();
+        storage.store_snapshot(&snapshot(0, 900)).unwrap();
+
+        let stored = storage.get_range("XBT/USD", timestamp, timestamp).unwrap();
+        let sequences: Vec<_> = stored.iter().map(|snapshot| snapshot.sequence).collect();
+        assert_eq!(sequences, vec![Some(7), Some(0)]);
+        let at = storage.get_at_time("XBT/USD", timestamp).unwrap().unwrap();
+        assert_eq!(at.sequence, Some(0), "the latest received wins");
+
+        // Keys written before the receive time was added end at the exchange time
+        let legacy = snapshot(3, 0);
+        let earlier = timestamp - chrono::Duration::seconds(1);
+        let key = format!("XBT/USD:{}", earlier.timestamp_nanos_opt().unwrap());
+        let value = serde_json::to_vec(&OrderbookSnapshot { timestamp: earlier, ..legacy }).unwrap();
+        storage.db.insert(key.as_bytes(), value).unwrap();
+        let at = storage.get_at_time("XBT/USD", earlier).unwrap().unwrap();
+        assert_eq!(at.sequence, Some(3));
+
+        drop(storage);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn keeps_trades_without_an_id_across_restarts() {
+        let path = std::env::temp_dir().join(format!("orderbook-trades-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+
+        let timestamp = Utc::now();
+        let trade = |volume: i64| Trade {
+            symbol: "XBT/USD".to_string(),
+            price: Decimal::from(50_000),
+            volume: Decimal::from(volume),
+            side: TradeSide::Buy,
+            order_type: TradeOrderType::Market,
+            timestamp,
+            trade_id: None,
+        };
+        let storage = OrderbookStorage::new(path.to_str().unwrap()).unwrap();
+        storage.store_trade(&trade(1)).unwrap();
+        drop(storage);
+        let storage = OrderbookStorage::new(path.to_str().unwrap()).unwrap();
+        storage.store_trade(&trade(2)).unwrap();
+
+        let trades = storage.get_trades("XBT/USD", timestamp, timestamp, 10).unwrap();
+        assert_eq!(trades.len(), 2, "the trade stored before the restart is kept");
+
+        drop(storage);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
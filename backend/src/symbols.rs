@@ -0,0 +1,373 @@
+//! Trading pair names and metadata
+//!
+//! Kraken names the same pair differently depending on the API: `XBT/USD` on
+//! WebSocket v1, `BTC/USD` on v2, `XXBTZUSD` (or the altname `XBTUSD`) on
+//! REST, and users tend to type `btc-usd`. [`Symbol`] is the canonical form
+//! used everywhere inside the backend, including storage keys; it is always
+//! displayed in v1 naming. [`SymbolRegistry`] resolves every other spelling
+//! and carries per-pair trading metadata, which can be loaded from a saved
+//! Kraken `AssetPairs` response.
+
+pub mod asset_pairs;
+
+pub use asset_pairs::{load_asset_pairs, parse_asset_pairs};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Assets whose v2 (ISO) code differs from the legacy v1 code, as (v1, v2)
+const ASSET_ALIASES: [(&str, &str); 2] = [("XBT", "BTC"), ("XDG", "DOGE")];
+
+/// Trading pair in canonical (Kraken v1) naming
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol {
+    base: String,
+    quote: String,
+}
+
+impl Symbol {
+    /// Build a symbol from asset codes in either naming (`BTC` or `XBT`)
+    pub fn new(base: &str, quote: &str) -> Self {
+        Self {
+            base: v1_asset(base),
+            quote: v1_asset(quote),
+        }
+    }
+
+    /// Parse `BASE/QUOTE`, `BASE-QUOTE` or `BASE_QUOTE` in v1 or v2 naming, case-insensitively
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let mut assets = name.trim().split(['/', '-', '_']);
+        match (assets.next(), assets.next(), assets.next()) {
+            (Some(base), Some(quote), None) if is_asset(base) && is_asset(quote) => Ok(Self::new(base, quote)),
+            _ => Err(format!("Invalid pair {} (expected BASE/QUOTE, e.g. XBT/USD)", name)),
+        }
+    }
+
+    /// Parse a v2 symbol such as `BTC/USD`
+    pub fn from_v2(name: &str) -> Result<Self, String> {
+        Self::parse(name)
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    pub fn quote(&self) -> &str {
+        &self.quote
+    }
+
+    /// Name used by WebSocket v1 and as storage key (`XBT/USD`)
+    pub fn v1_name(&self) -> String {
+        format!("{}/{}", self.base, self.quote)
+    }
+
+    /// Name used by WebSocket v2 (`BTC/USD`)
+    pub fn v2_name(&self) -> String {
+        format!("{}/{}", v2_asset(&self.base), v2_asset(&self.quote))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
+
+impl std::str::FromStr for Symbol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        other.split_once('/') == Some((self.base.as_str(), self.quote.as_str()))
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Self::parse(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+fn is_asset(code: &str) -> bool {
+    !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '.')
+}
+
+fn v1_asset(code: &str) -> String {
+    let code = code.trim().to_ascii_uppercase();
+    match ASSET_ALIASES.iter().find(|(_, v2)| *v2 == code) {
+        Some((v1, _)) => v1.to_string(),
+        None => code,
+    }
+}
+
+fn v2_asset(code: &str) -> &str {
+    match ASSET_ALIASES.iter().find(|(v1, _)| *v1 == code) {
+        Some((_, v2)) => v2,
+        None => code,
+    }
+}
+
+/// Metadata of a tradable pair
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolInfo {
+    pub symbol: Symbol,
+    /// WebSocket v2 name (`BTC/USD`)
+    pub v2_name: String,
+    /// REST pair key (`XXBTZUSD`)
+    pub rest_name: String,
+    /// REST alternate name (`XBTUSD`)
+    pub altname: String,
+    /// Smallest price increment
+    pub tick_size: Decimal,
+    /// Smallest volume increment
+    pub lot_size: Decimal,
+    /// Decimal places of prices
+    pub price_precision: u32,
+    /// Decimal places of volumes
+    pub lot_decimals: u32,
+    /// Smallest order volume, if known
+    pub min_volume: Option<Decimal>,
+    /// Smallest order value in the quote currency, if known
+    pub min_cost: Option<Decimal>,
+}
+
+impl SymbolInfo {
+    /// Metadata for `symbol` with the given price and volume decimals
+    pub fn new(symbol: Symbol, rest_name: &str, price_precision: u32, lot_decimals: u32) -> Self {
+        Self {
+            v2_name: symbol.v2_name(),
+            rest_name: rest_name.to_string(),
+            altname: format!("{}{}", symbol.base(), symbol.quote()),
+            tick_size: Decimal::new(1, price_precision),
+            lot_size: Decimal::new(1, lot_decimals),
+            price_precision,
+            lot_decimals,
+            min_volume: None,
+            min_cost: None,
+            symbol,
+        }
+    }
+
+    /// Round a price to the nearest tick
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        if self.tick_size.is_zero() {
+            return price.round_dp(self.price_precision);
+        }
+        ((price / self.tick_size).round() * self.tick_size).round_dp(self.price_precision)
+    }
+
+    /// Check an order against the pair's precision and minimums
+    pub fn validate_order(&self, volume: Decimal, price: Option<Decimal>) -> Result<(), String> {
+        if volume.normalize().scale() > self.lot_decimals {
+            return Err(format!(
+                "Volume {} has more than {} decimals for {}",
+                volume, self.lot_decimals, self.symbol
+            ));
+        }
+        if let Some(min_volume) = self.min_volume {
+            if volume < min_volume {
+                return Err(format!("Volume {} is below the minimum {} for {}", volume, min_volume, self.symbol));
+            }
+        }
+        if let Some(price) = price {
+            if price <= Decimal::ZERO {
+                return Err("Price must be positive".to_string());
+            }
+            if price.normalize().scale() > self.price_precision {
+                return Err(format!(
+                    "Price {} has more than {} decimals for {}",
+                    price, self.price_precision, self.symbol
+                ));
+            }
+            if let Some(min_cost) = self.min_cost {
+                if price * volume < min_cost {
+                    return Err(format!(
+                        "Order value {} is below the minimum {} {} for {}",
+                        price * volume,
+                        min_cost,
+                        self.symbol.quote(),
+                        self.symbol
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Known pairs, looked up by any of their names
+#[derive(Debug, Clone, Default)]
+pub struct SymbolRegistry {
+    symbols: Vec<SymbolInfo>,
+    /// Normalized name -> index into `symbols`
+    names: HashMap<String, usize>,
+}
+
+impl SymbolRegistry {
+    /// Registry of the pairs tracked by default
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        for (base, quote, rest_name, price_precision, lot_decimals) in [
+            ("XBT", "USD", "XXBTZUSD", 1, 8),
+            ("ETH", "USD", "XETHZUSD", 2, 8),
+            ("SOL", "USD", "SOLUSD", 2, 8),
+            ("XDG", "USD", "XDGUSD", 7, 8),
+            ("ETH", "XBT", "XETHXXBT", 5, 8),
+        ] {
+            registry.register(SymbolInfo::new(
+                Symbol::new(base, quote),
+                rest_name,
+                price_precision,
+                lot_decimals,
+            ));
+        }
+        registry
+    }
+
+    /// Add a pair, replacing any earlier entry for the same symbol
+    pub fn register(&mut self, info: SymbolInfo) {
+        let index = match self.symbols.iter().position(|s| s.symbol == info.symbol) {
+            Some(index) => {
+                self.names.retain(|_, i| *i != index);
+                self.symbols[index] = info;
+                index
+            }
+            None => {
+                self.symbols.push(info);
+                self.symbols.len() - 1
+            }
+        };
+
+        let info = &self.symbols[index];
+        let names = [
+            info.symbol.v1_name(),
+            info.v2_name.clone(),
+            info.v2_name.replace('/', ""),
+            info.rest_name.clone(),
+            info.altname.clone(),
+        ];
+        for name in &names {
+            self.names.insert(normalize(name), index);
+        }
+    }
+
+    /// Register every pair of a saved `AssetPairs` response, returning how many were loaded
+    pub fn load_asset_pairs(&mut self, path: &Path) -> Result<usize, String> {
+        let pairs = load_asset_pairs(path)?;
+        let count = pairs.len();
+        for info in pairs {
+            self.register(info);
+        }
+        Ok(count)
+    }
+
+    /// Canonical symbol for any known spelling, or any well-formed `BASE/QUOTE`
+    pub fn resolve(&self, name: &str) -> Result<Symbol, String> {
+        match self.names.get(&normalize(name)) {
+            Some(&index) => Ok(self.symbols[index].symbol.clone()),
+            None => Symbol::parse(name),
+        }
+    }
+
+    /// Canonical storage key for a pair given as two path segments
+    pub fn key(&self, base: &str, quote: &str) -> String {
+        let name = format!("{}/{}", base, quote);
+        self.resolve(&name).map(|symbol| symbol.v1_name()).unwrap_or(name)
+    }
+
+    /// Metadata for a pair, if known
+    pub fn info(&self, symbol: &Symbol) -> Option<&SymbolInfo> {
+        self.symbols.iter().find(|s| &s.symbol == symbol)
+    }
+
+    /// All known pairs
+    pub fn iter(&self) -> impl Iterator<Item = &SymbolInfo> {
+        self.symbols.iter()
+    }
+}
+
+/// Case- and separator-insensitive lookup key
+fn normalize(name: &str) -> String {
+    let mut assets: Vec<String> = name
+        .trim()
+        .split(['/', '-', '_'])
+        .map(|asset| asset.to_ascii_uppercase())
+        .collect();
+    if assets.len() == 2 {
+        assets.iter_mut().for_each(|asset| *asset = v1_asset(asset));
+    }
+    assets.concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_names() {
+        let xbt = Symbol::new("XBT", "USD");
+        for name in ["XBT/USD", "BTC/USD", "btc-usd", "xbt_usd"] {
+            assert_eq!(Symbol::parse(name).unwrap(), xbt, "{}", name);
+        }
+        assert_eq!(xbt.v1_name(), "XBT/USD");
+        assert_eq!(xbt.v2_name(), "BTC/USD");
+        assert_eq!(Symbol::from_v2("DOGE/USD").unwrap().to_string(), "XDG/USD");
+        assert!(Symbol::parse("XBTUSD").is_err());
+        assert!(Symbol::parse("XBT/USD/EUR").is_err());
+    }
+
+    #[test]
+    fn resolves_registered_names() {
+        let registry = SymbolRegistry::builtin();
+        let xbt = Symbol::new("XBT", "USD");
+        for name in ["XXBTZUSD", "XBTUSD", "xbtusd", "BTCUSD", "BTC/USD", "XBT/USD"] {
+            assert_eq!(registry.resolve(name).unwrap(), xbt, "{}", name);
+        }
+        assert_eq!(registry.key("BTC", "USD"), "XBT/USD");
+        assert_eq!(registry.resolve("ADA/EUR").unwrap().to_string(), "ADA/EUR");
+        assert!(registry.resolve("ADAEUR").is_err());
+
+        let info = registry.info(&xbt).unwrap();
+        assert_eq!(info.tick_size, Decimal::new(1, 1));
+        assert_eq!(info.lot_size, Decimal::new(1, 8));
+    }
+
+    #[test]
+    fn rounds_and_validates_orders() {
+        let dec = |s: &str| s.parse::<Decimal>().unwrap();
+        let mut info = SymbolInfo::new(Symbol::new("XBT", "USD"), "XXBTZUSD", 1, 8);
+        info.tick_size = dec("0.5");
+        info.min_volume = Some(dec("0.0001"));
+        info.min_cost = Some(dec("0.5"));
+
+        assert_eq!(info.round_price(dec("64000.26")), dec("64000.5"));
+        assert_eq!(info.round_price(dec("64000.24")), dec("64000.0"));
+
+        assert!(info.validate_order(dec("0.01"), Some(dec("64000.5"))).is_ok());
+        assert!(info.validate_order(dec("0.00001"), None).unwrap_err().contains("below the minimum"));
+        assert!(info.validate_order(dec("0.123456789"), None).unwrap_err().contains("decimals"));
+        assert!(info.validate_order(dec("0.01"), Some(dec("64000.25"))).unwrap_err().contains("decimals"));
+        assert!(info.validate_order(dec("0.0001"), Some(dec("1.0"))).unwrap_err().contains("Order value"));
+    }
+}
@@ -0,0 +1,97 @@
+//! Kraken `AssetPairs` metadata files
+//!
+//! Reads a saved response of `GET /0/public/AssetPairs`:
+//!
+//! ```text
+//! {"error":[],"result":{"XXBTZUSD":{"altname":"XBTUSD","wsname":"XBT/USD","pair_decimals":1,
+//!  "lot_decimals":8,"ordermin":"0.00005","costmin":"0.5","tick_size":"0.1",...},...}}
+//! ```
+//!
+//! Pairs without a `wsname` (dark pool books) cannot be streamed and are skipped.
+
+use crate::symbols::{Symbol, SymbolInfo};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+struct AssetPairsResponse {
+    #[serde(default)]
+    error: Vec<String>,
+    #[serde(default)]
+    result: BTreeMap<String, AssetPair>,
+}
+
+/// One entry of the `result` object; unknown fields are ignored
+#[derive(Debug, Deserialize)]
+struct AssetPair {
+    altname: String,
+    wsname: Option<String>,
+    pair_decimals: u32,
+    lot_decimals: u32,
+    ordermin: Option<Decimal>,
+    costmin: Option<Decimal>,
+    tick_size: Option<Decimal>,
+}
+
+/// Parse an `AssetPairs` response body into pair metadata
+pub fn parse_asset_pairs(json: &str) -> Result<Vec<SymbolInfo>, String> {
+    let response: AssetPairsResponse = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    if !response.error.is_empty() {
+        return Err(format!("AssetPairs response has errors: {}", response.error.join(", ")));
+    }
+
+    let mut pairs = Vec::new();
+    for (rest_name, pair) in response.result {
+        let Some(wsname) = pair.wsname else {
+            continue;
+        };
+        let symbol = Symbol::parse(&wsname).map_err(|e| format!("{}: {}", rest_name, e))?;
+
+        let mut info = SymbolInfo::new(symbol, &rest_name, pair.pair_decimals, pair.lot_decimals);
+        info.altname = pair.altname;
+        if let Some(tick_size) = pair.tick_size {
+            info.tick_size = tick_size;
+        }
+        info.min_volume = pair.ordermin;
+        info.min_cost = pair.costmin;
+        pairs.push(info);
+    }
+    Ok(pairs)
+}
+
+/// Read pair metadata from a saved `AssetPairs` response
+pub fn load_asset_pairs(path: &Path) -> Result<Vec<SymbolInfo>, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_asset_pairs(&json).map_err(|e| format!("Invalid AssetPairs file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const ASSET_PAIRS: &str = include_str!("../../tests/fixtures/kraken_rest/asset_pairs.json");
+
+    #[test]
+    fn parses_asset_pairs_response() {
+        let pairs = parse_asset_pairs(ASSET_PAIRS).unwrap();
+        let names: Vec<String> = pairs.iter().map(|p| p.symbol.to_string()).collect();
+        assert_eq!(names, ["ADA/EUR", "ETH/USD", "XBT/USD"], "dark pool pairs are skipped");
+
+        let xbt = &pairs[2];
+        assert_eq!(xbt.rest_name, "XXBTZUSD");
+        assert_eq!(xbt.altname, "XBTUSD");
+        assert_eq!(xbt.price_precision, 1);
+        assert_eq!(xbt.lot_decimals, 8);
+        assert_eq!(xbt.tick_size, Decimal::from_str("0.1").unwrap());
+        assert_eq!(xbt.min_volume, Some(Decimal::from_str("0.00005").unwrap()));
+        assert_eq!(xbt.min_cost, Some(Decimal::from_str("0.5").unwrap()));
+    }
+
+    #[test]
+    fn rejects_error_responses() {
+        assert!(parse_asset_pairs(r#"{"error":["EQuery:Unknown asset pair"]}"#).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Synthetic cross-pair books implied from two legs
+//!
+//! A synthetic `BASE/QUOTE` book is implied from `BASE/VIA` and `QUOTE/VIA`:
+//! buying BASE with QUOTE means selling QUOTE for VIA and buying BASE with it,
+//! so the implied asks come from the base leg's asks and the quote leg's bids
+//! (and the implied bids the other way round). Levels are matched by the VIA
+//! amount they trade, so the implied volumes account for the depth of both
+//! legs.
+
+use crate::exchange::Venue;
+use crate::feed::DEFAULT_BOOK_DEPTH;
+use crate::storage::{OrderbookSnapshot, PriceLevel};
+use crate::symbols::Symbol;
+use rust_decimal::RoundingStrategy;
+use serde::{Deserialize, Serialize};
+
+/// Decimal places of implied prices for pairs without known metadata
+pub const DEFAULT_PRICE_DECIMALS: u32 = 10;
+
+/// Decimal places of implied volumes
+const VOLUME_DECIMALS: u32 = 8;
+
+/// Unit implied volumes are expressed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyntheticVolume {
+    /// Amount of the base asset, like a real book
+    #[default]
+    Base,
+    /// Amount of the quote asset it trades for
+    Quote,
+}
+
+impl std::str::FromStr for SyntheticVolume {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "base" => Ok(Self::Base),
+            "quote" => Ok(Self::Quote),
+            other => Err(format!("Unknown synthetic volume unit: {}", other)),
+        }
+    }
+}
+
+/// Synthetic pair implied from two Kraken books sharing the `via` asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyntheticPair {
+    pub symbol: Symbol,
+    /// Asset both legs are quoted in (e.g. `USD`)
+    pub via: String,
+    /// Maximum implied levels per side
+    pub depth: usize,
+    pub volume: SyntheticVolume,
+}
+
+impl SyntheticPair {
+    pub fn new(symbol: Symbol, via: &str, depth: usize, volume: SyntheticVolume) -> Result<Self, String> {
+        // Parsing the base leg validates and normalizes the asset code
+        let via = Symbol::parse(&format!("{}/{}", symbol.base(), via))?.quote().to_string();
+        if via == symbol.base() || via == symbol.quote() {
+            return Err(format!("{} cannot be implied via one of its own assets ({})", symbol, via));
+        }
+        if depth == 0 {
+            return Err(format!("Synthetic depth for {} must be positive", symbol));
+        }
+        Ok(Self {
+            symbol,
+            via,
+            depth,
+            volume,
+        })
+    }
+
+    /// Parse a `PAIR@VIA[:DEPTH[:VOLUME]]` entry such as `ETH/XBT@USD:10:quote`
+    pub fn parse(entry: &str) -> Result<Self, String> {
+        let (pair, rest) = entry
+            .trim()
+            .split_once('@')
+            .ok_or_else(|| format!("Invalid synthetic pair {} (expected PAIR@VIA, e.g. ETH/XBT@USD)", entry))?;
+        let mut fields = rest.split(':');
+        let via = fields.next().unwrap_or_default();
+        let depth = match fields.next() {
+            Some(depth) => depth.parse().map_err(|_| format!("Invalid depth in {}", entry))?,
+            None => DEFAULT_BOOK_DEPTH,
+        };
+        let volume = match fields.next() {
+            Some(volume) => volume.parse()?,
+            None => SyntheticVolume::default(),
+        };
+        if fields.next().is_some() {
+            return Err(format!("Invalid synthetic pair {}", entry));
+        }
+        Self::new(Symbol::parse(pair)?, via, depth, volume)
+    }
+
+    /// Synthetic pairs listed in `SYNTHETIC_PAIRS` (comma-separated)
+    pub fn from_env() -> Result<Vec<Self>, String> {
+        match std::env::var("SYNTHETIC_PAIRS") {
+            Ok(value) => value
+                .split(',')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(Self::parse)
+                .collect(),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// `BASE/VIA`
+    pub fn base_leg(&self) -> Symbol {
+        Symbol::new(self.symbol.base(), &self.via)
+    }
+
+    /// `QUOTE/VIA`
+    pub fn quote_leg(&self) -> Symbol {
+        Symbol::new(self.symbol.quote(), &self.via)
+    }
+
+    /// Whether `symbol` is one of the legs
+    pub fn uses(&self, symbol: &str) -> bool {
+        self.base_leg() == symbol || self.quote_leg() == symbol
+    }
+
+    /// Implied book from the current books of both legs, with prices rounded to `price_decimals`
+    pub fn implied(&self, base_leg: &OrderbookSnapshot, quote_leg: &OrderbookSnapshot, price_decimals: u32) -> OrderbookSnapshot {
+        OrderbookSnapshot {
+            venue: Venue::Synthetic,
+            symbol: self.symbol.to_string(),
+            timestamp: base_leg.timestamp.max(quote_leg.timestamp),
+            received_at: base_leg.received_at.max(quote_leg.received_at),
+            // Rounded against the taker, so the implied book never looks better than the legs
+            bids: self.side(&base_leg.bids, &quote_leg.asks, price_decimals, RoundingStrategy::ToNegativeInfinity),
+            asks: self.side(&base_leg.asks, &quote_leg.bids, price_decimals, RoundingStrategy::ToPositiveInfinity),
+            checksum: None,
+            sequence: None,
+            stale: base_leg.stale || quote_leg.stale,
+        }
+    }
+
+    fn side(&self, first: &[PriceLevel], second: &[PriceLevel], price_decimals: u32, rounding: RoundingStrategy) -> Vec<PriceLevel> {
+        ImpliedSide {
+            first,
+            second,
+            depth: self.depth,
+            volume: self.volume,
+            price_decimals,
+            rounding,
+        }
+        .levels()
+    }
+}
+
+/// One side of an implied book: `first` levels of the base leg matched
+/// against `second` levels of the quote leg, both best first
+struct ImpliedSide<'a> {
+    first: &'a [PriceLevel],
+    second: &'a [PriceLevel],
+    depth: usize,
+    volume: SyntheticVolume,
+    price_decimals: u32,
+    rounding: RoundingStrategy,
+}
+
+impl ImpliedSide<'_> {
+    fn levels(&self) -> Vec<PriceLevel> {
+        let mut levels: Vec<PriceLevel> = Vec::new();
+        // Each level with the VIA amount still left at it
+        let mut first = self.first.iter().map(|level| (level, level.price * level.volume));
+        let mut second = self.second.iter().map(|level| (level, level.price * level.volume));
+        let (mut a, mut b) = (first.next(), second.next());
+
+        while let (Some((level_a, left_a)), Some((level_b, left_b))) = (a, b) {
+            let amount = left_a.min(left_b);
+            let unit_price = match self.volume {
+                SyntheticVolume::Base => level_a.price,
+                SyntheticVolume::Quote => level_b.price,
+            };
+            let (Some(price), Some(volume)) = (level_a.price.checked_div(level_b.price), amount.checked_div(unit_price))
+            else {
+                break;
+            };
+            let price = price.round_dp_with_strategy(self.price_decimals, self.rounding);
+            let volume = volume.round_dp_with_strategy(VOLUME_DECIMALS, RoundingStrategy::ToZero);
+
+            match levels.last_mut() {
+                // Distinct leg prices can round to the same implied price
+                Some(last) if last.price == price => last.volume += volume,
+                _ => {
+                    if levels.len() == self.depth {
+                        break;
+                    }
+                    levels.push(PriceLevel {
+                        price,
+                        volume,
+                        order_count: None,
+                        timestamp: level_a.timestamp.max(level_b.timestamp),
+                    });
+                }
+            }
+
+            a = if left_a == amount { first.next() } else { Some((level_a, left_a - amount)) };
+            b = if left_b == amount { second.next() } else { Some((level_b, left_b - amount)) };
+        }
+
+        levels.retain(|level| !level.volume.is_zero());
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn book(symbol: &str, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderbookSnapshot {
+        let levels = |levels: &[(&str, &str)]| {
+            levels
+                .iter()
+                .map(|&(price, volume)| PriceLevel {
+                    price: dec(price),
+                    volume: dec(volume),
+                    order_count: None,
+                    timestamp: None,
+                })
+                .collect()
+        };
+        OrderbookSnapshot {
+            venue: Venue::Kraken,
+            symbol: symbol.to_string(),
+            timestamp: Utc::now(),
+            received_at: None,
+            bids: levels(bids),
+            asks: levels(asks),
+            checksum: None,
+            sequence: None,
+            stale: false,
+        }
+    }
+
+    fn levels(levels: &[PriceLevel]) -> Vec<(Decimal, Decimal)> {
+        levels.iter().map(|level| (level.price, level.volume)).collect()
+    }
+
+    #[test]
+    fn parses_entries() {
+        let pair = SyntheticPair::parse("ETH/BTC@usd:10:quote").unwrap();
+        assert_eq!(pair.symbol, "ETH/XBT");
+        assert_eq!(pair.via, "USD");
+        assert_eq!(pair.depth, 10);
+        assert_eq!(pair.volume, SyntheticVolume::Quote);
+        assert_eq!(pair.base_leg(), "ETH/USD");
+        assert_eq!(pair.quote_leg(), "XBT/USD");
+        assert!(pair.uses("XBT/USD"));
+
+        let defaults = SyntheticPair::parse("ETH/XBT@USD").unwrap();
+        assert_eq!((defaults.depth, defaults.volume), (DEFAULT_BOOK_DEPTH, SyntheticVolume::Base));
+
+        assert!(SyntheticPair::parse("ETH/XBT").is_err());
+        assert!(SyntheticPair::parse("ETH/XBT@XBT").is_err());
+        assert!(SyntheticPair::parse("ETH/XBT@USD:0").is_err());
+        assert!(SyntheticPair::parse("ETH/XBT@USD:10:usd").is_err());
+    }
+
+    #[test]
+    fn implies_levels_across_the_depth_of_both_legs() {
+        let pair = SyntheticPair::parse("ETH/XBT@USD:10").unwrap();
+        let eth = book("ETH/USD", &[("2000", "1"), ("1990", "2")], &[("2010", "1.5")]);
+        let xbt = book("XBT/USD", &[("40000", "0.1"), ("39900", "1")], &[("40100", "0.02"), ("40200", "1")]);
+
+        let implied = pair.implied(&eth, &xbt, 5);
+
+        assert_eq!(implied.venue, Venue::Synthetic);
+        assert_eq!(implied.symbol, "ETH/XBT");
+        // 2000 USD of ETH buys 802 USD of XBT at 40100, the rest at 40200
+        assert_eq!(
+            levels(&implied.bids),
+            vec![
+                (dec("0.04987"), dec("0.401")),
+                (dec("0.04975"), dec("0.599")),
+                (dec("0.0495"), dec("2")),
+            ]
+        );
+        // 3015 USD of ETH, paid for by selling 0.075375 XBT at 40000
+        assert_eq!(levels(&implied.asks), vec![(dec("0.05025"), dec("1.5"))]);
+    }
+
+    #[test]
+    fn limits_depth_and_converts_volume_to_quote() {
+        let eth = book("ETH/USD", &[("2000", "1"), ("1990", "2")], &[]);
+        let xbt = book("XBT/USD", &[], &[("40000", "1")]);
+
+        let base = SyntheticPair::parse("ETH/XBT@USD:1").unwrap().implied(&eth, &xbt, 5);
+        assert_eq!(levels(&base.bids), vec![(dec("0.05"), dec("1"))]);
+        assert!(base.asks.is_empty());
+
+        let quote = SyntheticPair::parse("ETH/XBT@USD:1:quote").unwrap().implied(&eth, &xbt, 5);
+        assert_eq!(levels(&quote.bids), vec![(dec("0.05"), dec("0.05"))]);
+    }
+}
@@ -3,6 +3,7 @@
 //! Provides secure server-side order execution.
 //! API keys are stored server-side, not exposed to the frontend.
 
+use crate::symbols::{Symbol, SymbolRegistry};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -16,7 +17,9 @@ pub struct TradingConfig {
     /// Maximum order size (safety limit)
     pub max_order_size: Decimal,
     /// Allowed trading pairs
-    pub allowed_pairs: Vec<String>,
+    pub allowed_pairs: Vec<Symbol>,
+    /// Resolves the pair names orders are placed with
+    pub symbols: Arc<SymbolRegistry>,
 }
 
 impl Default for TradingConfig {
@@ -25,10 +28,11 @@ impl Default for TradingConfig {
             live_enabled: false,
             max_order_size: Decimal::from(1),
             allowed_pairs: vec![
-                "XBT/USD".to_string(),
-                "ETH/USD".to_string(),
-                "SOL/USD".to_string(),
+                Symbol::new("XBT", "USD"),
+                Symbol::new("ETH", "USD"),
+                Symbol::new("SOL", "USD"),
             ],
+            symbols: Arc::new(SymbolRegistry::builtin()),
         }
     }
 }
@@ -134,25 +138,33 @@ impl TradingService {
     }
 
     pub async fn execute_order(&self, intent: OrderIntent) -> OrderResult {
-        if let Err(e) = self.validate_order(&intent) {
-            return OrderResult {
-                success: false,
-                mode: "paper".to_string(),
-                order_id: None,
-                message: "Validation failed".to_string(),
-                error: Some(e),
-            };
-        }
+        let pair = match self.validate_order(&intent) {
+            Ok(pair) => pair,
+            Err(e) => {
+                return OrderResult {
+                    success: false,
+                    mode: "paper".to_string(),
+                    order_id: None,
+                    message: "Validation failed".to_string(),
+                    error: Some(e),
+                }
+            }
+        };
 
         let order_id = format!("paper_{}", uuid::Uuid::new_v4());
+        // Prices must sit on the pair's tick grid
+        let price = match self.config.symbols.info(&pair) {
+            Some(info) => intent.price.map(|price| info.round_price(price)),
+            None => intent.price,
+        };
         
         let paper_order = PaperOrder {
             id: order_id.clone(),
-            pair: intent.pair.clone(),
+            pair: pair.to_string(),
             side: intent.side.clone(),
             order_type: intent.order_type.clone(),
             volume: intent.volume,
-            price: intent.price,
+            price,
             status: "filled".to_string(),
             created_at: chrono::Utc::now(),
         };
@@ -170,8 +182,8 @@ impl TradingService {
             intent.order_type.to_uppercase(),
             intent.side.to_uppercase(),
             intent.volume,
-            intent.pair,
-            intent.price.map(|p| p.to_string()).unwrap_or("MARKET".to_string())
+            pair,
+            price.map(|p| p.to_string()).unwrap_or("MARKET".to_string())
         );
 
         tracing::info!("{}", message);
@@ -185,8 +197,10 @@ impl TradingService {
         }
     }
 
-    fn validate_order(&self, intent: &OrderIntent) -> Result<(), String> {
-        if !self.config.allowed_pairs.contains(&intent.pair) {
+    /// Check an order against the safety limits, returning its canonical pair
+    fn validate_order(&self, intent: &OrderIntent) -> Result<Symbol, String> {
+        let pair = self.config.symbols.resolve(&intent.pair)?;
+        if !self.config.allowed_pairs.contains(&pair) {
             return Err(format!("Pair {} not allowed", intent.pair));
         }
         if intent.volume > self.config.max_order_size {
@@ -198,7 +212,10 @@ impl TradingService {
         if intent.order_type == "limit" && intent.price.is_none() {
             return Err("Limit orders require price".to_string());
         }
-        Ok(())
+        if let Some(info) = self.config.symbols.info(&pair) {
+            info.validate_order(intent.volume, intent.price)?;
+        }
+        Ok(pair)
     }
 
     pub async fn cancel_order(&self, txid: &str) -> OrderResult {
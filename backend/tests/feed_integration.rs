@@ -0,0 +1,505 @@
+//! End-to-end tests: the Kraken client and the API against a mock Kraken server
+
+mod mock_kraken;
+
+use mock_kraken::MockKraken;
+use orderbook_visualizer::api::{self, WsMessage};
+use orderbook_visualizer::consolidated::ConsolidatedBook;
+use orderbook_visualizer::delta::LevelAction;
+use orderbook_visualizer::exchange::Venue;
+use orderbook_visualizer::feed::{
+    feed_control, run_feed, BookSubscription, FeedConfig, SubscriptionStatus,
+};
+use orderbook_visualizer::orderbook_manager::{ConnectionState, ManagerCallback, OrderbookManager};
+use orderbook_visualizer::storage::{OrderbookSnapshot, PriceLevel};
+use orderbook_visualizer::synthetic::SyntheticPair;
+use orderbook_visualizer::trading::{TradingConfig, TradingService};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use warp::{Filter, Reply};
+
+const PAIR: &str = "XBT/USD";
+const DEPTH: usize = 10;
+
+/// Backend wired to a mock Kraken server, with storage in a scratch directory
+struct Backend {
+    manager: Arc<OrderbookManager>,
+    storage: PathBuf,
+}
+
+impl Backend {
+    fn start(mock: &MockKraken, name: &str) -> (Self, impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone) {
+        Self::start_with(mock, name, |_| {})
+    }
+
+    /// Start with the default test configuration adjusted by `configure`
+    fn start_with(
+        mock: &MockKraken,
+        name: &str,
+        configure: impl FnOnce(&mut FeedConfig),
+    ) -> (Self, impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone) {
+        let storage = std::env::temp_dir().join(format!("orderbook-it-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&storage);
+        let manager = Arc::new(OrderbookManager::new(storage.to_str().unwrap()).unwrap());
+
+        let mut config = FeedConfig {
+            subscriptions: vec![BookSubscription::new(PAIR, DEPTH).unwrap()],
+            url: Some(mock.url()),
+            trades: false,
+            ticker: false,
+            spread: false,
+            ohlc_interval: None,
+            reconnect_initial: Duration::from_millis(20),
+            reconnect_max: Duration::from_millis(100),
+            ..Default::default()
+        };
+        configure(&mut config);
+        let (feed, commands) = feed_control(config.subscriptions.clone());
+        let callback = Arc::new(ManagerCallback::new(manager.clone()));
+        tokio::spawn(run_feed(callback, config, commands));
+
+        let trading = Arc::new(tokio::sync::RwLock::new(TradingService::new(TradingConfig::default())));
+        let routes = api::routes(manager.clone(), feed, trading);
+
+        (Self { manager, storage }, routes)
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.storage);
+    }
+}
+
+/// Poll `condition` until it holds, failing the test after a few seconds
+async fn eventually(what: &str, condition: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+async fn get_json<F>(routes: &F, path: &str) -> Value
+where
+    F: Filter + 'static,
+    F::Extract: Reply + Send,
+{
+    let response = warp::test::request().path(path).reply(routes).await;
+    serde_json::from_slice(response.body()).unwrap()
+}
+
+async fn post_json<F>(routes: &F, path: &str, body: Value) -> Value
+where
+    F: Filter + 'static,
+    F::Extract: Reply + Send,
+{
+    let response = warp::test::request().method("POST").path(path).json(&body).reply(routes).await;
+    serde_json::from_slice(response.body()).unwrap()
+}
+
+fn dec(value: &str) -> Decimal {
+    value.parse().unwrap()
+}
+
+fn send_snapshot(mock: &MockKraken) {
+    mock.send_book(
+        PAIR,
+        DEPTH,
+        json!({
+            "as": [["5541.30000", "2.50700000", "1534614248.123678"], ["5541.80000", "0.33000000", "1534614098.345543"]],
+            "bs": [["5541.20000", "1.52900000", "1534614248.765567"], ["5539.90000", "0.30000000", "1534614241.769870"]]
+        }),
+    );
+}
+
+#[tokio::test]
+async fn serves_books_received_from_the_feed() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start(&mock, "rest");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    eventually("the subscription ack", || {
+        backend.manager.get_subscription_status(PAIR) == Some(SubscriptionStatus::Subscribed)
+    })
+    .await;
+    let pending = get_json(&routes, "/api/orderbook/XBT/USD").await;
+    assert_eq!(pending["error"], "Waiting for the first snapshot");
+
+    send_snapshot(&mock);
+    mock.send_heartbeat();
+    mock.send_book(PAIR, DEPTH, json!({ "b": [["5541.20000", "0.00000000", "1534614335.345903"]] }));
+    eventually("the update", || {
+        backend.manager.get_current(PAIR).is_some_and(|book| book.bids.len() == 1)
+    })
+    .await;
+
+    let book: OrderbookSnapshot = serde_json::from_value(get_json(&routes, "/api/orderbook/XBT/USD").await).unwrap();
+    assert_eq!(book.symbol, PAIR);
+    assert_eq!(book.bids[0].price, dec("5539.9"));
+    assert_eq!(book.asks[0].price, dec("5541.3"));
+    assert_eq!(book.asks[0].volume, dec("2.507"));
+    assert!(!book.stale);
+
+    let aliased = get_json(&routes, "/api/orderbook/BTC/USD").await;
+    assert_eq!(aliased["symbol"], PAIR, "v2 naming resolves to the same book");
+
+    let limited: OrderbookSnapshot =
+        serde_json::from_value(get_json(&routes, "/api/orderbook/XBT/USD?depth=1").await).unwrap();
+    assert_eq!(limited.asks.len(), 1);
+
+    let connection = get_json(&routes, "/api/connection").await;
+    assert_eq!(connection["state"], "connected");
+}
+
+#[tokio::test]
+async fn serves_consolidated_book_across_venues() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start(&mock, "consolidated");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    send_snapshot(&mock);
+    eventually("the snapshot", || backend.manager.get_current(PAIR).is_some()).await;
+
+    // A Coinbase bid above Kraken's best ask
+    let level = |price: &str, volume: &str| PriceLevel {
+        price: dec(price),
+        volume: dec(volume),
+        order_count: None,
+        timestamp: None,
+    };
+    backend.manager.update_orderbook_snapshot(OrderbookSnapshot {
+        venue: Venue::Coinbase,
+        symbol: PAIR.to_string(),
+        timestamp: chrono::Utc::now(),
+        received_at: None,
+        bids: vec![level("5541.50", "0.1")],
+        asks: vec![level("5542.00", "1.0")],
+        checksum: None,
+        sequence: Some(0),
+        stale: false,
+    });
+
+    let coinbase: OrderbookSnapshot =
+        serde_json::from_value(get_json(&routes, "/api/orderbook/XBT/USD?venue=coinbase").await).unwrap();
+    assert_eq!(coinbase.venue, Venue::Coinbase);
+    let kraken: OrderbookSnapshot = serde_json::from_value(get_json(&routes, "/api/orderbook/XBT/USD").await).unwrap();
+    assert_eq!(kraken.venue, Venue::Kraken);
+
+    let book: ConsolidatedBook =
+        serde_json::from_value(get_json(&routes, "/api/orderbook/XBT/USD?venue=all").await).unwrap();
+    assert_eq!(book.venues, vec![Venue::Kraken, Venue::Coinbase]);
+    let bids: Vec<_> = book.bids.iter().map(|l| (l.venue, l.level.price)).collect();
+    assert_eq!(
+        bids,
+        vec![(Venue::Coinbase, dec("5541.5")), (Venue::Kraken, dec("5541.2")), (Venue::Kraken, dec("5539.9"))]
+    );
+    assert_eq!(book.asks[0].venue, Venue::Kraken);
+    let arbitrage = book.arbitrage.expect("Coinbase bids above Kraken's ask");
+    assert_eq!((arbitrage.buy_venue, arbitrage.sell_venue), (Venue::Kraken, Venue::Coinbase));
+    assert_eq!(arbitrage.spread, dec("0.2"));
+    assert_eq!(arbitrage.volume, dec("0.1"));
+
+    let limited: ConsolidatedBook =
+        serde_json::from_value(get_json(&routes, "/api/orderbook/XBT/USD?venue=all&depth=1").await).unwrap();
+    assert_eq!((limited.bids.len(), limited.asks.len()), (1, 1));
+
+    let missing = get_json(&routes, "/api/orderbook/ETH/USD?venue=all").await;
+    assert_eq!(missing["error"], "No venue has a book for this symbol");
+}
+
+#[tokio::test]
+async fn implies_synthetic_books_from_two_legs() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start_with(&mock, "synthetic", |config| {
+        config.subscriptions.push(BookSubscription::new("ETH/USD", DEPTH).unwrap());
+    });
+    backend.manager.add_synthetic(SyntheticPair::parse("ETH/XBT@USD:5").unwrap());
+
+    eventually("the book subscriptions", || {
+        mock.book_subscriptions(PAIR) == 1 && mock.book_subscriptions("ETH/USD") == 1
+    })
+    .await;
+    send_snapshot(&mock);
+    eventually("the XBT/USD snapshot", || backend.manager.get_current(PAIR).is_some()).await;
+    let pending = get_json(&routes, "/api/orderbook/ETH/XBT").await;
+    assert_eq!(pending["error"], "No synthetic book for this symbol", "needs both legs");
+
+    mock.send_book(
+        "ETH/USD",
+        DEPTH,
+        json!({
+            "as": [["277.20000", "5.00000000", "1534614248.123678"]],
+            "bs": [["277.10000", "10.00000000", "1534614248.765567"]]
+        }),
+    );
+    eventually("the synthetic book", || backend.manager.get_venue_book(Venue::Synthetic, "ETH/XBT").is_some()).await;
+
+    // Served without a venue like a real pair, with prices at ETH/XBT precision
+    let book: OrderbookSnapshot = serde_json::from_value(get_json(&routes, "/api/orderbook/ETH/XBT").await).unwrap();
+    assert_eq!(book.venue, Venue::Synthetic);
+    assert_eq!((book.bids[0].price, book.bids[0].volume), (dec("0.05"), dec("10")));
+    assert_eq!((book.asks[0].price, book.asks[0].volume), (dec("0.05003"), dec("5")));
+
+    // Implied levels are not tradable liquidity, so they stay out of venue=all
+    let missing = get_json(&routes, "/api/orderbook/ETH/XBT?venue=all").await;
+    assert_eq!(missing["error"], "No venue has a book for this symbol");
+    backend.manager.update_orderbook_snapshot(OrderbookSnapshot {
+        venue: Venue::Coinbase,
+        bids: book.bids[..1].to_vec(),
+        asks: book.asks[..1].to_vec(),
+        ..book.clone()
+    });
+    let consolidated: ConsolidatedBook =
+        serde_json::from_value(get_json(&routes, "/api/orderbook/ETH/XBT?venue=all").await).unwrap();
+    assert_eq!(consolidated.venues, vec![Venue::Coinbase]);
+    assert!(consolidated.bids.iter().chain(&consolidated.asks).all(|level| level.venue == Venue::Coinbase));
+
+    let mut client = warp::test::ws()
+        .path("/ws/orderbook/ETH/XBT")
+        .handshake(routes.clone())
+        .await
+        .expect("websocket handshake");
+    let next = |message: warp::ws::Message| -> WsMessage { serde_json::from_str(message.to_str().unwrap()).unwrap() };
+    assert!(matches!(next(client.recv().await.unwrap()), WsMessage::Snapshot { data, .. } if data.venue == Venue::Synthetic));
+
+    // Thinner XBT/USD asks leave less ETH to sell into
+    mock.send_book(PAIR, DEPTH, json!({ "a": [["5541.30000", "0.00000000", "1534614335.345903"]] }));
+    match next(client.recv().await.unwrap()) {
+        WsMessage::Delta { data } => {
+            assert_eq!(data.symbol, "ETH/XBT");
+            assert_eq!(data.bids[0].action, LevelAction::Changed);
+            assert!(data.bids[0].level.volume < dec("10"), "a leg update recomputes the implied book");
+        }
+        other => panic!("expected an implied delta, got {:?}", other),
+    }
+
+    let request = warp::test::request().method("DELETE").path("/api/admin/subscriptions/ETH/USD");
+    request.reply(&routes).await;
+    assert!(backend.manager.get_venue_book(Venue::Synthetic, "ETH/XBT").is_none());
+    // Streaming clients are told the implied book is gone rather than left on its last levels
+    match next(client.recv().await.unwrap()) {
+        WsMessage::Delta { data } => {
+            assert!(data.reset && data.stale, "a stale reset empties the book");
+            assert!(data.apply(None).bids.is_empty());
+        }
+        other => panic!("expected the implied book to be retired, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn streams_snapshots_over_websocket() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start(&mock, "ws");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    send_snapshot(&mock);
+    eventually("the snapshot", || backend.manager.get_current(PAIR).is_some()).await;
+
+    let mut client = warp::test::ws()
+        .path("/ws/orderbook/XBT/USD?depth=1")
+        .handshake(routes.clone())
+        .await
+        .expect("websocket handshake");
+
+    let next = |message: warp::ws::Message| -> WsMessage { serde_json::from_str(message.to_str().unwrap()).unwrap() };
+
+    let sequence = match next(client.recv().await.unwrap()) {
+        WsMessage::Snapshot { data, sequence } => {
+            assert_eq!(data.bids.len(), 1, "depth query applies to the initial snapshot");
+            assert_eq!(data.bids[0].price, dec("5541.2"));
+            sequence
+        }
+        other => panic!("expected the current snapshot, got {:?}", other),
+    };
+
+    // Only the changed levels follow; the new best ask pushes the old one out of depth 1
+    mock.send_book(PAIR, DEPTH, json!({ "a": [["5541.25000", "1.00000000", "1534614335.345903"]] }));
+    match next(client.recv().await.unwrap()) {
+        WsMessage::Delta { data } => {
+            assert_eq!(data.sequence, sequence + 1);
+            assert!(data.bids.is_empty());
+            let asks: Vec<_> = data.asks.iter().map(|change| (change.action, change.level.price)).collect();
+            assert_eq!(asks, vec![(LevelAction::Added, dec("5541.25")), (LevelAction::Removed, dec("5541.3"))]);
+            assert_eq!(data.book_sequence, Some(1));
+        }
+        other => panic!("expected a delta, got {:?}", other),
+    }
+
+    // Without a depth limit the manager's delta is passed on as is
+    let mut full = warp::test::ws()
+        .path("/ws/orderbook/XBT/USD")
+        .handshake(routes)
+        .await
+        .expect("websocket handshake");
+    let mut book = match next(full.recv().await.unwrap()) {
+        WsMessage::Snapshot { data, .. } => data,
+        other => panic!("expected the current snapshot, got {:?}", other),
+    };
+    mock.send_book(PAIR, DEPTH, json!({ "b": [["5541.20000", "0.50000000", "1534614336.345903"]] }));
+    match next(full.recv().await.unwrap()) {
+        WsMessage::Delta { data } => {
+            assert_eq!(data.bids.len(), 1);
+            assert!(data.asks.is_empty());
+            book = data.apply(Some(book));
+        }
+        other => panic!("expected a delta, got {:?}", other),
+    }
+    let current = backend.manager.get_current(PAIR).unwrap();
+    assert_eq!(book.bids, current.bids, "applying deltas to the snapshot tracks the book");
+    assert_eq!(book.asks, current.asks);
+}
+
+#[tokio::test]
+async fn resyncs_websocket_clients_that_fall_behind() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start(&mock, "lagged");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    send_snapshot(&mock);
+    eventually("the snapshot", || backend.manager.get_current(PAIR).is_some()).await;
+
+    let mut client = warp::test::ws()
+        .path("/ws/orderbook/XBT/USD")
+        .handshake(routes)
+        .await
+        .expect("websocket handshake");
+    let next = |message: warp::ws::Message| -> WsMessage { serde_json::from_str(message.to_str().unwrap()).unwrap() };
+    assert!(matches!(next(client.recv().await.unwrap()), WsMessage::Snapshot { .. }));
+    let mut other_symbol = backend.manager.subscribe_updates("ETH/USD");
+
+    // Far more updates than a channel buffers, without yielding to the client's task
+    let mut book = backend.manager.get_current(PAIR).unwrap();
+    for i in 1..=1000 {
+        book.bids[0].volume = Decimal::from(i);
+        backend.manager.update_orderbook_snapshot(book.clone());
+    }
+
+    match next(client.recv().await.unwrap()) {
+        WsMessage::Snapshot { data, sequence } => {
+            assert_eq!(data.bids[0].volume, Decimal::from(1000));
+            assert_eq!(sequence, backend.manager.get_live_book(Venue::Kraken, PAIR).unwrap().sequence);
+        }
+        other => panic!("expected a fresh snapshot, got {:?}", other),
+    }
+    assert!(other_symbol.try_recv().is_err(), "updates stay on their symbol's channel");
+}
+
+#[tokio::test]
+async fn reports_rejected_subscriptions() {
+    let mock = MockKraken::start().await;
+    mock.reject(PAIR, "Currency pair not supported XBT/USD");
+    let (_backend, routes) = Backend::start(&mock, "rejected");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let body = get_json(&routes, "/api/orderbook/XBT/USD").await;
+        if body["subscription"]["state"] == "rejected" {
+            assert_eq!(body["error"], "Subscription rejected: Currency pair not supported XBT/USD");
+            break;
+        }
+        assert!(Instant::now() < deadline, "subscription was never rejected: {}", body);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn reconnects_and_resubscribes_after_close() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start(&mock, "reconnect");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    send_snapshot(&mock);
+    eventually("the snapshot", || backend.manager.get_current(PAIR).is_some()).await;
+
+    mock.close();
+    eventually("a second connection", || mock.connections() == 2).await;
+    eventually("the resubscription", || mock.book_subscriptions(PAIR) == 2).await;
+    eventually("the connected state", || backend.manager.connection_status().state == ConnectionState::Connected).await;
+
+    let connection = get_json(&routes, "/api/connection").await;
+    assert_eq!(connection["reconnects"], 1);
+}
+
+#[tokio::test]
+async fn resubscribes_after_checksum_mismatch() {
+    let mock = MockKraken::start().await;
+    let (backend, _routes) = Backend::start(&mock, "checksum");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    send_snapshot(&mock);
+    eventually("the snapshot", || backend.manager.get_current(PAIR).is_some()).await;
+
+    mock.send_book(
+        PAIR,
+        DEPTH,
+        json!({ "a": [["5541.30000", "1.00000000", "1534614335.345903"]], "c": "12345" }),
+    );
+    eventually("the resubscription", || mock.book_subscriptions(PAIR) == 2).await;
+    assert_eq!(mock.requests("unsubscribe").len(), 1);
+    assert_eq!(mock.connections(), 1, "a checksum mismatch does not drop the connection");
+}
+
+#[tokio::test]
+async fn resubscribes_after_crossed_book() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start(&mock, "crossed");
+
+    eventually("the book subscription", || mock.book_subscriptions(PAIR) == 1).await;
+    send_snapshot(&mock);
+    eventually("the snapshot", || backend.manager.get_current(PAIR).is_some()).await;
+
+    mock.send_book(PAIR, DEPTH, json!({ "b": [["5541.50000", "1.00000000", "1534614335.345903"]] }));
+    eventually("the resubscription", || mock.book_subscriptions(PAIR) == 2).await;
+    // Still in flight from the old subscription; must not trigger another resubscribe
+    mock.send_book(PAIR, DEPTH, json!({ "a": [["5541.40000", "1.00000000", "1534614336.345903"]] }));
+    send_snapshot(&mock);
+    mock.send_book(PAIR, DEPTH, json!({ "a": [["5541.25000", "1.00000000", "1534614337.345903"]] }));
+    eventually("an update on the fresh snapshot", || {
+        backend.manager.get_current(PAIR).is_some_and(|book| book.sequence == Some(1))
+    })
+    .await;
+
+    let stats = get_json(&routes, "/api/orderbook/XBT/USD/stats").await;
+    assert_eq!(stats["invalid_books"], 1);
+    assert_eq!(mock.book_subscriptions(PAIR), 2);
+}
+
+#[tokio::test]
+async fn shards_symbols_across_connections() {
+    let mock = MockKraken::start().await;
+    let (backend, routes) = Backend::start_with(&mock, "sharded", |config| {
+        config.subscriptions.push(BookSubscription::new("ETH/USD", DEPTH).unwrap());
+        config.symbols_per_connection = Some(1);
+    });
+
+    eventually("one connection per pair", || mock.connections() == 2).await;
+    eventually("the book subscriptions", || {
+        mock.book_subscriptions(PAIR) == 1 && mock.book_subscriptions("ETH/USD") == 1
+    })
+    .await;
+    assert!(
+        mock.requests("subscribe").iter().all(|request| request["pair"].as_array().unwrap().len() == 1),
+        "each connection subscribes only its own pair"
+    );
+    eventually("both connections up", || backend.manager.connection_status().connections.len() == 2).await;
+    let connection = get_json(&routes, "/api/connection").await;
+    assert_eq!(connection["state"], "connected");
+    assert_eq!(connection["connections"][1]["state"], "connected");
+
+    // A pair added at runtime opens another connection once the others are full
+    post_json(&routes, "/api/admin/subscriptions", json!({ "pair": "SOL/USD", "depth": DEPTH })).await;
+    eventually("a third connection", || mock.connections() == 3).await;
+    eventually("the SOL/USD subscription", || mock.book_subscriptions("SOL/USD") == 1).await;
+
+    // A connection emptied by an unsubscribe takes the next pair
+    let request = warp::test::request().method("DELETE").path("/api/admin/subscriptions/ETH/USD");
+    request.reply(&routes).await;
+    post_json(&routes, "/api/admin/subscriptions", json!({ "pair": "ADA/USD", "depth": DEPTH })).await;
+    eventually("the ADA/USD subscription", || mock.book_subscriptions("ADA/USD") == 1).await;
+    assert_eq!(mock.connections(), 3);
+}
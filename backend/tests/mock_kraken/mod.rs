@@ -0,0 +1,218 @@
+//! In-process mock of the Kraken v1 WebSocket API
+//!
+//! Listens on a local port, answers `subscribe`, `unsubscribe` and `ping`
+//! requests the way Kraken does, and sends whatever frames a test scripts to
+//! every open connection.
+
+#![allow(dead_code)]
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Something a test asks the mock to do on every open connection
+#[derive(Debug, Clone)]
+enum Scripted {
+    Frame(String),
+    Close,
+}
+
+/// State shared by all connections
+#[derive(Default)]
+struct MockState {
+    connections: AtomicUsize,
+    next_channel_id: AtomicU64,
+    /// Every request received, in order
+    requests: Mutex<Vec<Value>>,
+    /// Pairs whose subscriptions fail, with the error message to send
+    rejected: Mutex<HashMap<String, String>>,
+}
+
+/// Running mock server; it stops when the test's runtime shuts down
+pub struct MockKraken {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    script: broadcast::Sender<Scripted>,
+}
+
+impl MockKraken {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind mock Kraken");
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(MockState::default());
+        let (script, _) = broadcast::channel(256);
+
+        let accept_state = state.clone();
+        let accept_script = script.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, accept_state.clone(), accept_script.subscribe()));
+            }
+        });
+
+        Self { addr, state, script }
+    }
+
+    /// WebSocket URL to point the client at
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    /// Answer subscriptions for `pair` with an error
+    pub fn reject(&self, pair: &str, message: &str) {
+        self.state
+            .rejected
+            .lock()
+            .unwrap()
+            .insert(pair.to_string(), message.to_string());
+    }
+
+    /// Send a raw frame
+    pub fn send(&self, frame: impl Into<String>) {
+        let _ = self.script.send(Scripted::Frame(frame.into()));
+    }
+
+    /// Send a book message for `pair`; `payload` holds the `as`/`bs` or `a`/`b`/`c` fields
+    pub fn send_book(&self, pair: &str, depth: usize, payload: Value) {
+        self.send(json!([0, payload, format!("book-{}", depth), pair]).to_string());
+    }
+
+    pub fn send_heartbeat(&self) {
+        self.send(json!({ "event": "heartbeat" }).to_string());
+    }
+
+    /// Close every open connection
+    pub fn close(&self) {
+        let _ = self.script.send(Scripted::Close);
+    }
+
+    /// Number of connections accepted so far
+    pub fn connections(&self) -> usize {
+        self.state.connections.load(Ordering::SeqCst)
+    }
+
+    /// Requests received with the given `event`
+    pub fn requests(&self, event: &str) -> Vec<Value> {
+        self.state
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request["event"] == event)
+            .cloned()
+            .collect()
+    }
+
+    /// Book subscribe requests received for `pair`
+    pub fn book_subscriptions(&self, pair: &str) -> usize {
+        self.requests("subscribe")
+            .iter()
+            .filter(|request| request["subscription"]["name"] == "book")
+            .filter(|request| request["pair"].as_array().is_some_and(|pairs| pairs.iter().any(|p| p == pair)))
+            .count()
+    }
+}
+
+async fn serve(stream: TcpStream, state: Arc<MockState>, mut script: broadcast::Receiver<Scripted>) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    state.connections.fetch_add(1, Ordering::SeqCst);
+    let (mut tx, mut rx) = ws.split();
+
+    let status = json!({
+        "connectionID": 8628615390848610000u64,
+        "event": "systemStatus",
+        "status": "online",
+        "version": "1.9.0"
+    });
+    if tx.send(Message::Text(status.to_string())).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            message = rx.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    for reply in state.respond(&text) {
+                        if tx.send(Message::Text(reply)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            scripted = script.recv() => match scripted {
+                Ok(Scripted::Frame(frame)) => {
+                    if tx.send(Message::Text(frame)).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(Scripted::Close) => {
+                    let _ = tx.send(Message::Close(None)).await;
+                    return;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+impl MockState {
+    /// Replies Kraken would send to a client request
+    fn respond(&self, text: &str) -> Vec<String> {
+        let Ok(request) = serde_json::from_str::<Value>(text) else {
+            return vec![json!({ "event": "error", "errorMessage": "Malformed request" }).to_string()];
+        };
+        self.requests.lock().unwrap().push(request.clone());
+
+        match request["event"].as_str() {
+            Some("ping") => vec![json!({ "event": "pong", "reqid": request["reqid"] }).to_string()],
+            Some(event @ ("subscribe" | "unsubscribe")) => {
+                let subscription = &request["subscription"];
+                let name = subscription["name"].as_str().unwrap_or_default();
+                let channel_name = match (name, subscription["depth"].as_u64(), subscription["interval"].as_u64()) {
+                    ("book", Some(depth), _) => format!("book-{}", depth),
+                    ("ohlc", _, Some(interval)) => format!("ohlc-{}", interval),
+                    _ => name.to_string(),
+                };
+                let pairs = request["pair"].as_array().cloned().unwrap_or_default();
+                let rejected = self.rejected.lock().unwrap();
+
+                pairs
+                    .iter()
+                    .map(|pair| {
+                        let pair = pair.as_str().unwrap_or_default();
+                        match rejected.get(pair) {
+                            Some(message) => json!({
+                                "errorMessage": message,
+                                "event": "subscriptionStatus",
+                                "pair": pair,
+                                "status": "error",
+                                "subscription": subscription
+                            }),
+                            None => json!({
+                                "channelID": self.next_channel_id.fetch_add(1, Ordering::SeqCst),
+                                "channelName": channel_name,
+                                "event": "subscriptionStatus",
+                                "pair": pair,
+                                "status": if event == "subscribe" { "subscribed" } else { "unsubscribed" },
+                                "subscription": subscription
+                            }),
+                        }
+                        .to_string()
+                    })
+                    .collect()
+            }
+            _ => vec![json!({ "event": "error", "errorMessage": "Unsupported event" }).to_string()],
+        }
+    }
+}
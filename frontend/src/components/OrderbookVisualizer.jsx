@@ -25,6 +25,38 @@ const getPriceDecimals = (symbol) => PAIR_DECIMALS[symbol] || PAIR_DECIMALS['def
 
 const PLAYBACK_SPEEDS = [0.5, 1, 2, 4];
 
+// Apply one side of a book delta: removed levels drop out, others replace the level at their price
+const applyLevelChanges = (levels, changes, descending) => {
+  const byPrice = new Map(levels.map((level) => [Number(level.price), level]));
+  changes.forEach(({ action, ...level }) => {
+    if (action === 'removed') {
+      byPrice.delete(Number(level.price));
+    } else {
+      byPrice.set(Number(level.price), level);
+    }
+  });
+  return [...byPrice.entries()]
+    .sort(([a], [b]) => (descending ? b - a : a - b))
+    .map(([, level]) => level);
+};
+
+// Book after a delta; a reset delta rebuilds it from nothing
+const applyDelta = (book, delta) => {
+  const base = delta.reset || !book ? { bids: [], asks: [] } : book;
+  return {
+    ...base,
+    venue: delta.venue,
+    symbol: delta.symbol,
+    timestamp: delta.timestamp,
+    received_at: delta.received_at,
+    checksum: delta.checksum,
+    sequence: delta.book_sequence,
+    stale: delta.stale,
+    bids: applyLevelChanges(base.bids, delta.bids, true),
+    asks: applyLevelChanges(base.asks, delta.asks, false),
+  };
+};
+
 // Default API URL - uses environment variable in production
 const DEFAULT_API_URL = process.env.REACT_APP_API_URL || 'http://localhost:3033';
 
@@ -158,23 +190,32 @@ const OrderbookVisualizer = ({
       const receiveTime = Date.now();
       try {
         const message = JSON.parse(event.data);
-        if (message.type === 'snapshot') {
+        if (message.type === 'snapshot' || message.type === 'delta') {
           // Calculate latency
           if (message.data.timestamp) {
             const serverTime = new Date(message.data.timestamp).getTime();
             setLatencyMs(receiveTime - serverTime);
           }
           setLastUpdateTime(receiveTime);
-          setIsStale(false);
+          // Backend flags books as stale while its exchange feed is down
+          setIsStale(Boolean(message.data.stale));
           // Trigger mid price pulse
           setMidPriceUpdated(true);
           setTimeout(() => setMidPriceUpdated(false), 400);
           
           // Store previous orderbook for change detection
+          // Deltas only carry the levels that changed since the last message
           setOrderbook((prev) => {
             setPrevOrderbook(prev);
-            return message.data;
+            return message.type === 'delta' ? applyDelta(prev, message.data) : message.data;
           });
+        } else if (message.type === 'subscription') {
+          // Surface rejected pairs instead of waiting for a snapshot forever
+          if (message.status.state === 'rejected') {
+            setError(`Subscription rejected: ${message.status.reason}`);
+          } else {
+            setError(null);
+          }
         } else if (message.type === 'error') {
           setError(message.message);
         }